@@ -125,6 +125,9 @@ pub struct Simulation {
     pub processes: HashMap<String, Process>,
     pub optimize: Vec<String>,
     pub optimize_time: bool,
+    // Duration given on the command line, the time a schedule's makespan is weighed against.
+    // Parsed simulations have none, the scorers fall back on their horizon
+    pub delay: Option<usize>,
 }
 
 impl From<SimulationBuilder> for Simulation {
@@ -141,6 +144,7 @@ impl From<SimulationBuilder> for Simulation {
             processes,
             optimize: s.optimize,
             optimize_time: s.optimize_time,
+            delay: None,
         }
     }
 }
@@ -154,6 +158,14 @@ impl Simulation {
             processes,
             optimize,
             optimize_time,
+            delay: None,
+        }
+    }
+
+    pub fn with_delay(self, delay: usize) -> Self {
+        Self {
+            delay: Some(delay),
+            ..self
         }
     }
 }
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	inventory::Inventory,
+	score::{Score, Scorer, BroScore, Values},
+	solver::{Production, Path, batchify},
+	simulate::get_available_steps,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	// 1 makes it a greedy search
+	width: usize,
+	max_depth: usize,
+	time_weight: f32,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			width: 10,
+			max_depth: 100,
+			time_weight: 0.1,
+			scorer: BroScore::default(),
+			values: Values::new(),
+		}
+	}
+}
+
+impl Config {
+	pub fn new(width: usize, max_depth: usize, time_weight: f32, scorer: BroScore, values: Values) -> Self {
+		Self { width, max_depth, time_weight, scorer, values }
+	}
+
+	pub fn greedy(self) -> Self {
+		Self { width: 1, ..self }
+	}
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control) -> Result<Production, String> {
+	let scorer = Scorer::new(simulation.clone(), config.time_weight, config.scorer).with_values(config.values.clone()).with_horizon(config.max_depth);
+	let mut beam: Vec<(Path, Inventory)> = vec![(vec![], simulation.inventory.clone())];
+	let mut best: (Score, Path) = (scorer.score(&vec![])?, vec![]);
+
+	for _ in 0..config.max_depth {
+		if control.is_cancelled() {
+			break
+		}
+		let mut candidates: Vec<(Score, Path, Inventory)> = vec![];
+		for (path, inventory) in &beam {
+			let available_steps = get_available_steps(&simulation, inventory);
+			for (step, updated_inventory) in available_steps {
+				let mut candidate = path.clone();
+				candidate.push(step);
+				let score = scorer.score(&candidate)?;
+				candidates.push((score, candidate, updated_inventory));
+			}
+		}
+		if candidates.is_empty() {
+			break
+		}
+		// Stable sort keeps the alphabetical order between equal scores
+		candidates.sort_by(|(score_a, _, _), (score_b, _, _)| { score_b.cmp(score_a) });
+		candidates.truncate(config.width.max(1));
+		if candidates[0].0 > best.0 {
+			control.incumbent(&candidates[0].1);
+			best = (candidates[0].0, candidates[0].1.clone());
+		}
+		beam = candidates
+			.into_iter()
+			.map(|(_, path, inventory)| { (path, inventory) })
+			.collect();
+	}
+	batchify(&simulation, best.1)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use crate::ast::parse;
+	use crate::check::{check, Output};
+	use crate::control::Control;
+	use crate::score::{BroScore, Scorer, Values};
+	use crate::solver::flatten;
+	use super::{solve, Config};
+
+	#[test]
+	fn test_beam_is_doable_and_no_worse_than_greedy() {
+		for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo);
+			let mut scores = vec![];
+			for width in &[1, 10] {
+				let config = Config::new(*width, 50, 0.1, BroScore::Leo, Values::new());
+				let path = flatten(&solve(simulation.clone(), config, &Control::default()).unwrap());
+				assert!(check(simulation.clone(), Output { steps: path.clone() }).is_ok(), "{} {}", name, width);
+				scores.push(scorer.score(&path).unwrap());
+			}
+			assert!(scores[1] >= scores[0], "{}: {} < {}", name, scores[1], scores[0]);
+		}
+	}
+}
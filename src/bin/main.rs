@@ -1,20 +1,110 @@
 use std::fs::File;
 use std::io::Read;
+use std::time::Duration;
 
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use serde_json::Value;
 
 use krpsim::{
-    ast::{parse},
-	solver::{solve, Production, Path},
+    ast::{parse, Simulation},
+	solver::{solve, flatten, Algorithm},
+	control::{CancelToken, Control},
+	genetic_config_parser::{read_config, read_checkpoint_config},
+	throughput::analyse,
+	tune::{tune, best_config, default_space, Options, Search, Space},
+	nsga2::{Front, Pick},
 	utils::generalize_error,
 	check::{Output, check}
 };
 
+struct Args {
+	file_path: String,
+	delay: usize,
+	algorithm: Algorithm,
+	throughput: bool,
+	time_limit: Option<Duration>,
+	seed: Option<u64>,
+	config_path: Option<String>,
+	resume: Option<String>,
+	overrides: Vec<String>,
+}
+
+struct TuneArgs {
+	file_paths: Vec<String>,
+	options: Options,
+	space_path: Option<String>,
+	config_path: Option<String>,
+	overrides: Vec<String>,
+	output_path: String,
+}
+
+struct PickArgs {
+	front_path: String,
+	pick: Pick,
+}
+
+enum Command {
+	Solve(Args),
+	Tune(TuneArgs),
+	Pick(PickArgs),
+}
+
+fn overrides(matches: &ArgMatches) -> Vec<String> {
+	matches
+		.values_of("set")
+		.map(|values| { values.map(|value| { value.to_string() }).collect() })
+		.unwrap_or_default()
+}
+
+fn parse_tune_args(matches: &ArgMatches) -> Result<TuneArgs, String> {
+	let parse_number = |name: &str, default: &str| -> Result<u64, String> {
+		let value = matches.value_of(name).unwrap_or(default);
+		value.parse().map_err(|_| { format!("Invalid {}: {}", name, value) })
+	};
+	let search = match matches.value_of("search").unwrap_or("random") {
+		"grid" => Search::Grid,
+		_ => Search::Random(parse_number("trials", "20")? as usize),
+	};
+	Ok(TuneArgs {
+		file_paths: matches
+			.values_of("FILES")
+			.ok_or("No simulation file to tune on")?
+			.map(|path| { path.to_string() })
+			.collect(),
+		options: Options {
+			search,
+			halving: matches.is_present("halving"),
+			seeds: parse_number("seeds", "3")?,
+			seed: parse_number("seed", "0")?,
+		},
+		space_path: matches.value_of("space").map(|path| { path.to_string() }),
+		config_path: matches.value_of("config").map(|path| { path.to_string() }),
+		overrides: overrides(matches),
+		output_path: matches.value_of("output").unwrap_or("tuned_config.json").to_string(),
+	})
+}
+
+fn parse_pick_args(matches: &ArgMatches) -> Result<PickArgs, String> {
+	let pick = match (matches.value_of("index"), matches.value_of("weights")) {
+		(Some (index), _) => Pick::Index(index.parse().map_err(|_| { format!("Invalid index: {}", index) })?),
+		(None, Some (weights)) => Pick::Weights(weights
+			.split(',')
+			.map(|weight| { weight.trim().parse().map_err(|_| { format!("Invalid weight: {}", weight) }) })
+			.collect::<Result<Vec<f64>, String>>()?),
+		(None, None) => Pick::default(),
+	};
+	Ok(PickArgs {
+		front_path: matches.value_of("FRONT").ok_or("No front to pick from")?.to_string(),
+		pick,
+	})
+}
+
 // Todo: error if no delay ?
-fn parse_args() -> Result<(String, usize), String> {
+fn parse_args() -> Result<Command, String> {
     let matches = App::new("krpsim")
         .author("Hugo Sabourin <hsabouri@student.42.fr>")
         .about("Process optimizer")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("FILE")
             .help("Input description file.")
             .required(true)
@@ -23,8 +113,127 @@ fn parse_args() -> Result<(String, usize), String> {
             .help("Duration of the simulation")
             .required(false)
             .index(2))
+        .arg(Arg::with_name("algorithm")
+            .help("Solver to use")
+            .short("a")
+            .long("algorithm")
+            .takes_value(true)
+            .possible_values(&["genetic", "mcts", "annealing", "tabu", "planner", "greedy", "beam", "nsga2", "portfolio"])
+            .default_value("genetic"))
+        .arg(Arg::with_name("throughput")
+            .help("Print the maximum sustainable production rate instead of solving")
+            .long("throughput"))
+        .arg(Arg::with_name("time-limit")
+            .help("Seconds after which the solver returns its best production so far")
+            .short("t")
+            .long("time-limit")
+            .takes_value(true))
+        .arg(Arg::with_name("seed")
+            .help("Seed of the random generator, to replay a run")
+            .short("s")
+            .long("seed")
+            .takes_value(true))
+        .arg(Arg::with_name("config")
+            .help("Solver configuration file, generic_config.json by default")
+            .short("c")
+            .long("config")
+            .takes_value(true))
+        .arg(Arg::with_name("resume")
+            .help("Genetic checkpoint to continue, its config replaces the configuration file")
+            .long("resume")
+            .takes_value(true)
+            .conflicts_with("config"))
+        .arg(Arg::with_name("set")
+            .help("Overrides a configuration value, such as mcts.iterations=500")
+            .long("set")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("plot")
+            .help("Plots the genetic scores of each generation to an svg or png file")
+            .long("plot")
+            .takes_value(true))
+        .arg(Arg::with_name("stats")
+            .help("Writes the genetic statistics to a csv or json file")
+            .long("stats")
+            .takes_value(true))
+        .subcommand(SubCommand::with_name("tune")
+            .about("Searches the genetic config scoring best over the simulations")
+            .arg(Arg::with_name("FILES")
+                .help("Simulation files to tune on")
+                .required(true)
+                .multiple(true))
+            .arg(Arg::with_name("search")
+                .help("Draws candidates at random or tries every combination")
+                .long("search")
+                .takes_value(true)
+                .possible_values(&["random", "grid"])
+                .default_value("random"))
+            .arg(Arg::with_name("trials")
+                .help("Candidates of the random search")
+                .long("trials")
+                .takes_value(true)
+                .default_value("20"))
+            .arg(Arg::with_name("halving")
+                .help("Evaluates candidates with fewer iterations first and drops the worst half each round")
+                .long("halving"))
+            .arg(Arg::with_name("seeds")
+                .help("Runs of each candidate on each simulation")
+                .long("seeds")
+                .takes_value(true)
+                .default_value("3"))
+            .arg(Arg::with_name("seed")
+                .help("Seed of the random search")
+                .short("s")
+                .long("seed")
+                .takes_value(true)
+                .default_value("0"))
+            .arg(Arg::with_name("space")
+                .help("JSON file mapping config keys to their candidate values")
+                .long("space")
+                .takes_value(true))
+            .arg(Arg::with_name("config")
+                .help("Base configuration, generic_config.json by default")
+                .short("c")
+                .long("config")
+                .takes_value(true))
+            .arg(Arg::with_name("set")
+                .help("Overrides a value of the base configuration")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(Arg::with_name("output")
+                .help("Where to write the best config, use it with --config")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .default_value("tuned_config.json")))
+        .subcommand(SubCommand::with_name("pick")
+            .about("Picks a schedule from a front saved by nsga2")
+            .arg(Arg::with_name("FRONT")
+                .help("Front file, see nsga2.front in the configuration")
+                .required(true))
+            .arg(Arg::with_name("index")
+                .help("Position of the schedule in the front")
+                .short("i")
+                .long("index")
+                .takes_value(true)
+                .conflicts_with("weights"))
+            .arg(Arg::with_name("weights")
+                .help("Comma separated weight of each objective, all equal by default")
+                .short("w")
+                .long("weights")
+                .takes_value(true)))
         .get_matches();
 
+    if let Some (tune_matches) = matches.subcommand_matches("tune") {
+        return Ok(Command::Tune(parse_tune_args(tune_matches)?))
+    }
+    if let Some (pick_matches) = matches.subcommand_matches("pick") {
+        return Ok(Command::Pick(parse_pick_args(pick_matches)?))
+    }
+
     let delay = matches
         .value_of("DELAY")
         .unwrap_or("0")
@@ -34,33 +243,141 @@ fn parse_args() -> Result<(String, usize), String> {
     let file_path = matches
         .value_of("FILE")
         .ok_or("Unable to open configuration file")?;
+
+    let algorithm = matches
+        .value_of("algorithm")
+        .unwrap_or("genetic")
+        .parse()?;
+
+    let time_limit = match matches.value_of("time-limit") {
+        Some (seconds) => {
+            let seconds: f64 = seconds.parse().map_err(|_| format!("Invalid time limit: {}", seconds))?;
+            if seconds <= 0. || seconds.is_nan() {
+                return Err(format!("Time limit must be positive: {}", seconds))
+            }
+            Some(Duration::from_secs_f64(seconds))
+        },
+        None => None,
+    };
+
+    let seed = match matches.value_of("seed") {
+        Some (seed) => Some(seed.parse().map_err(|_| format!("Invalid seed: {}", seed))?),
+        None => None,
+    };
+
+    // Given to the genetic solver through its configuration
+    let mut overrides = overrides(&matches);
+    for key in &["plot", "stats"] {
+        if let Some (path) = matches.value_of(key) {
+            if !matches!(algorithm, Algorithm::Genetic) {
+                return Err(format!("Only genetic runs have --{}", key))
+            }
+            overrides.push(format!("{}={}", key, Value::from(path)));
+        }
+    }
 	
-	Ok((file_path.to_string(), delay))
+	Ok(Command::Solve(Args {
+		file_path: file_path.to_string(),
+		delay,
+		algorithm,
+		throughput: matches.is_present("throughput"),
+		time_limit,
+		seed,
+		config_path: matches.value_of("config").map(|path| { path.to_string() }),
+		resume: matches.value_of("resume").map(|path| { path.to_string() }),
+		overrides,
+	}))
 }
 
-fn krpsim() -> Result<Production, String> {
-	let (simulation_file_path, _delay) = parse_args()?;
+fn read_simulation(simulation_file_path: &str) -> Result<Simulation, String> {
 	let mut simulation_file = File::open(simulation_file_path).map_err(generalize_error)?;
 	let mut simulation_content = String::new();
 	simulation_file.read_to_string(&mut simulation_content).unwrap();
-	let simulation = parse(simulation_content)?;
-	let result = solve(simulation.clone())?;
-	let final_path:Vec<Path> = result.clone().into_iter().map(|(_, path)| { path }).collect();
-	let flat_path = final_path.into_iter().fold(vec![], |acc, curr| { [&acc[..], &curr[..]].concat() });
-	let output = Output { steps: flat_path };
+	parse(simulation_content)
+}
+
+fn krpsim(args: Args) -> Result<(), String> {
+	let simulation = match args.delay {
+		// Without a delay the makespan is weighed against the solver's horizon
+		0 => read_simulation(&args.file_path)?,
+		delay => read_simulation(&args.file_path)?.with_delay(delay),
+	};
+	if args.throughput {
+		print!("{}", analyse(&simulation)?);
+		return Ok(())
+	}
+	let config_content = match &args.resume {
+		Some (path) => {
+			if !matches!(args.algorithm, Algorithm::Genetic) {
+				return Err("Only genetic runs can be resumed".to_string())
+			}
+			read_checkpoint_config(path, &args.overrides)?
+		},
+		None => read_config(args.config_path.as_deref(), &args.overrides)?,
+	};
+	let token = match args.time_limit {
+		Some (time_limit) => CancelToken::new().with_time_limit(time_limit),
+		None => CancelToken::new(),
+	};
+	let (result, seed, cache, summary) = solve(simulation.clone(), args.algorithm, &config_content, &Control::new(token), args.seed)?;
+	if let Some (cache) = cache {
+		eprint!("{}", cache);
+	}
+	if let Some (summary) = summary {
+		eprint!("{}", summary);
+	}
+	let output = Output { steps: flatten(&result) };
 	let final_inventory = check(simulation, output)?;
-	Ok(result)
-	
+	println!("{:?}", result);
+	println!("seed: {}", seed);
+	Ok(())
+}
+
+fn krpsim_tune(args: TuneArgs) -> Result<(), String> {
+	let simulations = args.file_paths
+		.iter()
+		.map(|path| { read_simulation(path) })
+		.collect::<Result<Vec<Simulation>, String>>()?;
+	let space: Space = match &args.space_path {
+		Some (path) => {
+			let content = std::fs::read_to_string(path).map_err(|err| { format!("{}: {}", path, err) })?;
+			serde_json::from_str(&content).map_err(|err| { format!("{}: {}", path, err) })?
+		},
+		None => default_space(),
+	};
+	let base_config = read_config(args.config_path.as_deref(), &args.overrides)?;
+	let results = tune(args.file_paths.clone(), &simulations, &base_config, &space, &args.options, &Control::default())?;
+	print!("{}", results);
+	std::fs::write(&args.output_path, best_config(&base_config, &results)?)
+		.map_err(|err| { format!("{}: {}", args.output_path, err) })?;
+	println!("Best config written to {}", args.output_path);
+	Ok(())
+}
+
+fn krpsim_pick(args: PickArgs) -> Result<(), String> {
+	let front = Front::load(&args.front_path)?;
+	let solution = front.pick(&args.pick)?;
+	let objectives: Vec<String> = solution.objectives.resources
+		.iter()
+		.map(|quantity| { quantity.to_string() })
+		.chain(std::iter::once(solution.objectives.makespan.to_string()))
+		.collect();
+	println!("{:?}", solution.production);
+	println!("{}: {}", front.names.join(", "), objectives.join(", "));
+	Ok(())
 }
 
 fn main() {
-	let result = krpsim();
-	
-	match result {
-		Err (err) => println!("An error occurred: {:?}", err),
-		Ok (best_path) => {
-			println!("{:?}", best_path);
+	let result = parse_args().and_then(|command| {
+		match command {
+			Command::Solve (args) => krpsim(args),
+			Command::Tune (args) => krpsim_tune(args),
+			Command::Pick (args) => krpsim_pick(args),
 		}
+	});
+	
+	if let Err (err) = result {
+		println!("An error occurred: {:?}", err);
 	}
 }
 
@@ -40,6 +40,7 @@ fn happy_path() {
 		processes,
 		optimize: vec!(String::from("premium_chair")),
 		optimize_time: false,
+		delay: None,
 	};
 
 	let expected_inventory = hashmap!(
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration as WallDuration, Instant};
+
+use crate::solver::Path;
+
+// Shared stop flag with an optional deadline, clones cancel together
+#[derive(Clone, Default)]
+pub struct CancelToken {
+	cancelled: Arc<AtomicBool>,
+	deadline: Option<Instant>,
+	// Cancels this token too, not the other way round
+	parent: Option<Arc<CancelToken>>,
+}
+
+impl CancelToken {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// Same flag, stopping at the earliest of both deadlines
+	pub fn with_time_limit(&self, time_limit: WallDuration) -> Self {
+		let deadline = Instant::now() + time_limit;
+		Self {
+			cancelled: Arc::clone(&self.cancelled),
+			deadline: Some(self.deadline.map_or(deadline, |current| { current.min(deadline) })),
+			parent: self.parent.clone(),
+		}
+	}
+
+	// Own flag, also cancelled with this one, so it can be cancelled alone
+	pub fn child(&self) -> Self {
+		Self {
+			cancelled: Arc::new(AtomicBool::new(false)),
+			deadline: self.deadline,
+			parent: Some(Arc::new(self.clone())),
+		}
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+			|| self.deadline.is_some_and(|deadline| { Instant::now() >= deadline })
+			|| self.parent.as_ref().is_some_and(|parent| { parent.is_cancelled() })
+	}
+}
+
+pub type IncumbentCallback = Arc<dyn Fn(&Path) + Send + Sync>;
+pub type BestKnownCallback = Arc<dyn Fn() -> Option<Path> + Send + Sync>;
+
+// Given to the solvers: when to stop, who to tell about better paths, and where to find the
+// best path found by the solvers running beside them
+#[derive(Clone, Default)]
+pub struct Control {
+	pub token: CancelToken,
+	on_incumbent: Option<IncumbentCallback>,
+	best_known: Option<BestKnownCallback>,
+}
+
+impl Control {
+	pub fn new(token: CancelToken) -> Self {
+		Self {
+			token,
+			on_incumbent: None,
+			best_known: None,
+		}
+	}
+
+	pub fn on_incumbent(self, callback: IncumbentCallback) -> Self {
+		Self {
+			on_incumbent: Some(callback),
+			..self
+		}
+	}
+
+	pub fn with_best_known(self, callback: BestKnownCallback) -> Self {
+		Self {
+			best_known: Some(callback),
+			..self
+		}
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.token.is_cancelled()
+	}
+
+	// Best path of the other solvers, for the solvers able to carry on from it
+	pub fn best_known(&self) -> Option<Path> {
+		self.best_known.as_ref().and_then(|callback| { callback() })
+	}
+
+	// Called by the solvers each time their best path improves
+	pub fn incumbent(&self, path: &Path) {
+		if let Some(callback) = &self.on_incumbent {
+			callback(path);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::CancelToken;
+
+	#[test]
+	fn test_cancel_is_shared() {
+		let token = CancelToken::new();
+		let limited = token.with_time_limit(Duration::from_secs(3600));
+
+		assert!(!limited.is_cancelled());
+		token.cancel();
+		assert!(limited.is_cancelled());
+	}
+
+	#[test]
+	fn test_child_cancels_alone() {
+		let token = CancelToken::new();
+		let child = token.child();
+
+		child.cancel();
+		assert!(child.is_cancelled());
+		assert!(!token.is_cancelled());
+		let other = token.child();
+		token.cancel();
+		assert!(other.is_cancelled());
+	}
+
+	#[test]
+	fn test_time_limit() {
+		let token = CancelToken::new();
+		let expired = token.with_time_limit(Duration::from_secs(0));
+
+		assert!(expired.is_cancelled());
+		assert!(!token.is_cancelled());
+	}
+}
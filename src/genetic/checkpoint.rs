@@ -0,0 +1,88 @@
+use std::fs::{read_to_string, rename, write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+	score::Score,
+	solver::Path,
+};
+use super::{Adaptation, GeneticSolver, Stats};
+
+#[derive(Serialize, Deserialize)]
+pub struct IslandState {
+	// Seed of the island's next generation
+	seed: u64,
+	parents: Vec<Path>,
+	stats: Stats,
+	best: Option<(Score, Path)>,
+	adaptation: Adaptation,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+	pub config: Value,
+	// Generations done
+	pub generation: usize,
+	// Seed of the next migration
+	pub seed: u64,
+	pub best: Option<(Score, Path)>,
+	// Generations since the best path was found
+	pub stagnant_generations: usize,
+	pub islands: Vec<IslandState>,
+}
+
+impl Checkpoint {
+	pub(super) fn new(config: Value, generation: usize, seed: u64, best: &Option<(Score, Path)>, stagnant_generations: usize, solvers: &[GeneticSolver]) -> Self {
+		let islands = solvers
+			.iter()
+			.map(|solver| {
+				IslandState {
+					seed: solver.seed,
+					parents: solver.parents.clone(),
+					stats: solver.stats.clone(),
+					best: solver.best.clone(),
+					adaptation: solver.adaptation.clone(),
+				}
+			})
+			.collect();
+		Self {
+			config,
+			generation,
+			seed,
+			best: best.clone(),
+			stagnant_generations,
+			islands,
+		}
+	}
+
+	// Written next to the checkpoint first, a crash while writing keeps the previous one
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		let content = serde_json::to_string(self).map_err(|err| { err.to_string() })?;
+		let temporary_path = format!("{}.tmp", path);
+		write(&temporary_path, content).map_err(|err| { format!("{}: {}", temporary_path, err) })?;
+		rename(&temporary_path, path).map_err(|err| { format!("{}: {}", path, err) })
+	}
+
+	pub fn load(path: &str) -> Result<Self, String> {
+		let content = read_to_string(path).map_err(|err| { format!("{}: {}", path, err) })?;
+		serde_json::from_str(&content).map_err(|err| { format!("{}: {}", path, err) })
+	}
+
+	// Puts the solvers back in their saved state, returns the seed of the next migration
+	pub(super) fn restore(self, solvers: &mut [GeneticSolver]) -> Result<u64, String> {
+		if self.islands.len() != solvers.len() {
+			return Err(format!("The checkpoint has {} islands, the config {}", self.islands.len(), solvers.len()))
+		}
+		for (solver, island) in solvers.iter_mut().zip(self.islands) {
+			island.stats.validate()?;
+			solver.seed = island.seed;
+			solver.parents = island.parents;
+			solver.stats = island.stats;
+			solver.best = island.best;
+			island.adaptation.validate(&solver.mutation)?;
+			solver.adaptation = island.adaptation;
+		}
+		Ok(self.seed)
+	}
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+	// Head of one parent, tail of the other
+	#[default]
+	OnePoint,
+	// Middle of the second parent between the head and tail of the first
+	TwoPoint,
+	// Processes of the first parent, in the order they come in the second
+	Order,
+}
+
+// Ordered pair of cuts in 0..=len
+fn cuts<R: Rng>(len: usize, rng: &mut R) -> (usize, usize) {
+	let a = rng.gen_range(0, len + 1);
+	let b = rng.gen_range(0, len + 1);
+	(a.min(b), a.max(b))
+}
+
+impl Crossover {
+	// The child may not be doable, it has to be repaired. Genes are steps, or runs of a step
+	pub fn apply<T: Clone + Eq + Hash, R: Rng>(&self, first: &[T], second: &[T], rng: &mut R) -> Vec<T> {
+		match self {
+			Crossover::OnePoint => {
+				let i = rng.gen_range(0, first.len() + 1);
+				let j = rng.gen_range(0, second.len() + 1);
+				[&first[..i], &second[j..]].concat()
+			},
+			Crossover::TwoPoint => {
+				let (i_start, i_end) = cuts(first.len(), rng);
+				let (j_start, j_end) = cuts(second.len(), rng);
+				[&first[..i_start], &second[j_start..j_end], &first[i_end..]].concat()
+			},
+			Crossover::Order => order(first, second, rng),
+		}
+	}
+}
+
+// Keep a slice of the first parent in place, and fill around it with the rest of its
+// processes in the second parent's order. Processes missing from the second go last
+fn order<T: Clone + Eq + Hash, R: Rng>(first: &[T], second: &[T], rng: &mut R) -> Vec<T> {
+	let (start, end) = cuts(first.len(), rng);
+	let mut remaining: HashMap<&T, usize> = HashMap::new();
+	for gene in first[..start].iter().chain(first[end..].iter()) {
+		*remaining.entry(gene).or_insert(0) += 1;
+	}
+	let mut take = |gene: &&T| -> bool {
+		match remaining.get_mut(*gene) {
+			Some (count) if *count > 0 => {
+				*count -= 1;
+				true
+			},
+			_ => false,
+		}
+	};
+	let mut fill: Vec<T> = second.iter().filter(&mut take).cloned().collect();
+	fill.extend(first[..start].iter().chain(first[end..].iter()).filter(&mut take).cloned());
+	let tail = fill.split_off(start);
+	[&fill[..], &first[start..end], &tail[..]].concat()
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::solver::Path;
+	use super::Crossover;
+
+	fn to_path(steps: &[&str]) -> Path {
+		steps.iter().map(|step| { step.to_string() }).collect()
+	}
+
+	#[test]
+	fn test_order_keeps_processes() {
+		let first = to_path(&["a", "a", "b", "c", "d"]);
+		let second = to_path(&["d", "c", "e", "a", "b"]);
+		let mut rng = StdRng::seed_from_u64(0);
+
+		for _ in 0..20 {
+			let mut child = Crossover::Order.apply(&first, &second, &mut rng);
+			child.sort();
+			assert_eq!(child, first);
+		}
+	}
+
+	#[test]
+	fn test_point_crossovers_take_from_parents() {
+		let first = to_path(&["a", "a", "a"]);
+		let second = to_path(&["b", "b"]);
+		let mut rng = StdRng::seed_from_u64(0);
+
+		for crossover in &[Crossover::OnePoint, Crossover::TwoPoint] {
+			for _ in 0..20 {
+				let child = crossover.apply(&first, &second, &mut rng);
+				let from_first = child.iter().filter(|step| { *step == "a" }).count();
+				assert!(from_first <= first.len());
+				assert!(child.len() - from_first <= second.len());
+			}
+		}
+	}
+}
@@ -0,0 +1,308 @@
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	local_search::repair,
+	score::Score,
+	solver::{Production, Path, batchify},
+};
+use super::{Checkpoint, Config, GeneticSolver, Generation, Reason, Stats};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+	// Each island sends its migrants to the next one
+	#[default]
+	Ring,
+	// Each island sends its migrants to another island drawn at each migration
+	Random,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Islands {
+	// 1 evolves a single population
+	count: usize,
+	// Generations between migrations, 0 to never migrate
+	migration_interval: usize,
+	// Best parents sent by each island
+	migrants: usize,
+	topology: Topology,
+	// Overrides of the genetic config for each island, islands past the list use it as is
+	configs: Vec<Value>,
+}
+
+impl Default for Islands {
+	fn default() -> Self {
+		Self {
+			count: 1,
+			migration_interval: 5,
+			migrants: 1,
+			topology: Topology::default(),
+			configs: vec![],
+		}
+	}
+}
+
+impl Islands {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.count == 0 {
+			return Err("islands.count must be greater than 0".to_string())
+		}
+		if self.configs.len() > self.count {
+			return Err(format!("islands.configs has {} configs for {} islands", self.configs.len(), self.count))
+		}
+		if let Some (i) = self.configs.iter().position(|config| { !config.is_object() }) {
+			return Err(format!("islands.configs[{}] must be an object", i))
+		}
+		Ok(())
+	}
+
+	pub fn count(&self) -> usize {
+		self.count
+	}
+}
+
+impl Config {
+	// Config of each island, the island overrides applied to this one
+	pub fn island_configs(&self) -> Result<Vec<Config>, String> {
+		let mut base = serde_json::to_value(self).map_err(|err| { err.to_string() })?;
+		if let Some (base) = base.as_object_mut() {
+			base.remove("islands");
+		}
+		(0..self.islands.count)
+			.map(|i| {
+				let mut value = base.clone();
+				if let (Some (value), Some (Value::Object (overrides))) = (value.as_object_mut(), self.islands.configs.get(i)) {
+					for (key, override_value) in overrides {
+						value.insert(key.clone(), override_value.clone());
+					}
+				}
+				let config: Config = serde_json::from_value(value)
+					.map_err(|err| { format!("islands.configs[{}]: {}", i, err) })?;
+				config.validate().map_err(|err| { format!("islands.configs[{}]: {}", i, err) })?;
+				Ok(config)
+			})
+			.collect()
+	}
+}
+
+impl Stats {
+	// Generation stats over every island, islands kept apart in `islands`. Rates and deviations
+	// are averaged, distinct paths summed
+	pub fn from_islands(mut islands: Vec<Stats>) -> Self {
+		if islands.len() == 1 {
+			return islands.remove(0)
+		}
+		let generations = islands.iter().map(|stats| { stats.best_scores.len() }).max().unwrap_or(0);
+		let mut stats = Stats::new();
+		for generation in 0..generations {
+			let values = |field: fn(&Stats) -> &Vec<f64>| -> f64 {
+				let values: Vec<f64> = islands.iter().filter_map(|island| { field(island).get(generation).cloned() }).collect();
+				values.iter().sum::<f64>() / values.len().max(1) as f64
+			};
+			let averages: Vec<Score> = islands
+				.iter()
+				.filter_map(|island| { island.average_scores.get(generation).cloned() })
+				.collect();
+			let best = islands
+				.iter()
+				.filter_map(|island| {
+					island.best_scores.get(generation).map(|score| { (*score, island.best_paths.get(generation).cloned().unwrap_or_default()) })
+				})
+				.max_by_key(|(score, _)| { *score });
+			let worst = islands
+				.iter()
+				.filter_map(|island| { island.worst_scores.get(generation).cloned() })
+				.min()
+				.unwrap_or(0);
+			let (best_score, best_path) = best.unwrap_or_default();
+			stats.push(Generation {
+				average_score: (averages.iter().map(|score| { *score as i64 }).sum::<i64>() / averages.len().max(1) as i64) as Score,
+				best_score,
+				worst_score: worst,
+				score_deviation: values(|island| { &island.score_deviations }),
+				distinct_paths: islands.iter().filter_map(|island| { island.distinct_paths.get(generation) }).sum(),
+				edit_distance: values(|island| { &island.edit_distances }),
+				infeasible_fraction: values(|island| { &island.infeasible_fractions }),
+				evaluations_per_second: values(|island| { &island.evaluations_per_second }),
+				best_path,
+				mutation_chance: values(|island| { &island.mutation_chances }),
+			});
+		}
+		for island in &islands {
+			stats.cache.add(&island.cache);
+		}
+		stats.islands = islands;
+		stats
+	}
+}
+
+// Returns the seed of the next migration
+fn migrate(solvers: &mut [GeneticSolver], islands: &Islands, seed: u64) -> u64 {
+	let mut rng = StdRng::seed_from_u64(seed);
+	let count = solvers.len();
+	let emigrants: Vec<Vec<Path>> = solvers
+		.iter()
+		.map(|solver| { solver.parents.iter().take(islands.migrants).cloned().collect() })
+		.collect();
+	// Gathered first, so migrants landing on the same island don't replace each other
+	let mut immigrants: Vec<Vec<Path>> = vec![vec![]; count];
+	for (from, paths) in emigrants.into_iter().enumerate() {
+		let to = match islands.topology {
+			Topology::Ring => (from + 1) % count,
+			Topology::Random => (from + rng.gen_range(1, count)) % count,
+		};
+		immigrants[to].extend(paths);
+	}
+	for (solver, paths) in solvers.iter_mut().zip(immigrants) {
+		solver.immigrate(paths);
+	}
+	rng.gen()
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<(Production, Stats), String> {
+	let mut solvers = config.island_configs()?
+		.into_iter()
+		.map(|island_config| { GeneticSolver::new(island_config, simulation.clone(), rng.gen()) })
+		.collect::<Vec<GeneticSolver>>();
+	let mut migration_seed = rng.gen();
+	let mut generation = 0;
+	let mut best: Option<(Score, Path)> = None;
+	// Generations since the best path was found
+	let mut stagnant_generations = 0;
+	if let Some (resume) = &config.resume {
+		let checkpoint = Checkpoint::load(resume)?;
+		generation = checkpoint.generation;
+		best = checkpoint.best.clone();
+		stagnant_generations = checkpoint.stagnant_generations;
+		migration_seed = checkpoint.restore(&mut solvers)?;
+		if let Some ((_, path)) = &best {
+			control.incumbent(path);
+		}
+	}
+	let mut checkpoint_config = serde_json::to_value(&config).map_err(|err| { err.to_string() })?;
+	checkpoint_config["resume"] = Value::Null;
+	let save = |generation: usize, migration_seed: u64, best: &Option<(Score, Path)>, stagnant_generations: usize, solvers: &[GeneticSolver]| {
+		match &config.checkpoint {
+			Some (path) => Checkpoint::new(checkpoint_config.clone(), generation, migration_seed, best, stagnant_generations, solvers).save(path),
+			None => Ok(()),
+		}
+	};
+
+	let start = Instant::now();
+	let iterations = solvers.iter().map(|solver| { solver.iterations }).max().unwrap_or(0);
+	let mut termination = Reason::Iterations;
+	// Last path taken from the solvers running beside this one
+	let mut known: Option<Path> = None;
+	while generation < iterations {
+		if control.is_cancelled() {
+			termination = Reason::Cancelled;
+			break
+		}
+		if let Some (path) = control.best_known() {
+			let ours = best.as_ref().is_some_and(|(_, best_path)| { *best_path == path });
+			if !ours && known.as_ref() != Some(&path) {
+				for solver in solvers.iter_mut() {
+					let mut migrant = repair(&simulation, path.clone());
+					migrant.truncate(solver.max_depth);
+					solver.immigrate(vec![migrant]);
+				}
+				known = Some(path);
+			}
+		}
+		stagnant_generations += 1;
+		for solver in solvers.iter_mut().filter(|solver| { generation < solver.iterations }) {
+			solver.evolve()?;
+			if let Some ((score, path)) = &solver.best {
+				if best.as_ref().is_none_or(|(best_score, _)| { score > best_score }) {
+					control.incumbent(path);
+					best = Some((*score, path.clone()));
+					stagnant_generations = 0;
+				}
+			}
+		}
+		generation += 1;
+		if solvers.len() > 1 && config.islands.migration_interval > 0 && generation % config.islands.migration_interval == 0 {
+			migration_seed = migrate(&mut solvers, &config.islands, migration_seed);
+		}
+		if config.checkpoint_interval > 0 && generation % config.checkpoint_interval == 0 && generation < iterations {
+			save(generation, migration_seed, &best, stagnant_generations, &solvers)?;
+		}
+		let best_score = best.as_ref().map(|(score, _)| { *score });
+		if let Some (reason) = config.termination.reason(best_score, stagnant_generations, start.elapsed()) {
+			termination = reason;
+			break
+		}
+	}
+	// Also saved when stopped early, to continue later
+	save(generation, migration_seed, &best, stagnant_generations, &solvers)?;
+
+	let best_path = best.map(|(_, path)| { path }).unwrap_or_default();
+	let mut stats = Stats::from_islands(solvers
+		.into_iter()
+		.map(|solver| {
+			let mut stats = solver.stats;
+			stats.cache = solver.scorer.cache_stats();
+			stats
+		})
+		.collect());
+	stats.termination = Some(termination);
+	Ok((batchify(&simulation, best_path)?, stats))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use crate::ast::parse;
+	use super::{Config, GeneticSolver, Islands, Topology, migrate};
+
+	// Islands whose parents are tagged by the island they come from
+	fn solvers(count: usize) -> Vec<GeneticSolver> {
+		let simulation = parse(read_to_string("ressources/simple").unwrap()).unwrap();
+		(0..count)
+			.map(|island| {
+				let mut solver = GeneticSolver::new(Config::default(), simulation.clone(), island as u64);
+				solver.parents = (0..3).map(|rank| { vec![format!("{}-{}", island, rank)] }).collect();
+				solver
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_ring_migration() {
+		let mut solvers = solvers(3);
+		let islands = Islands { count: 3, migrants: 2, topology: Topology::Ring, ..Islands::default() };
+		migrate(&mut solvers, &islands, 0);
+		for (island, solver) in solvers.iter().enumerate() {
+			let from = (island + 2) % 3;
+			// The best parents of the previous island replace the worst ones
+			assert_eq!(solver.parents[0], vec![format!("{}-0", island)]);
+			assert_eq!(solver.parents[1..], [vec![format!("{}-0", from)], vec![format!("{}-1", from)]]);
+		}
+	}
+
+	#[test]
+	fn test_random_migration() {
+		let islands = Islands { count: 4, migrants: 1, topology: Topology::Random, ..Islands::default() };
+		let mut off_ring = false;
+		for seed in 0..10 {
+			let mut solvers = solvers(4);
+			migrate(&mut solvers, &islands, seed);
+			for from in 0..4 {
+				// The best parent of each island lands on exactly one other island
+				let migrant = vec![format!("{}-0", from)];
+				let to: Vec<usize> = (0..4).filter(|to| { *to != from && solvers[*to].parents.contains(&migrant) }).collect();
+				assert_eq!(to.len(), 1);
+				off_ring |= to[0] != (from + 1) % 4;
+			}
+		}
+		assert!(off_ring);
+	}
+}
@@ -0,0 +1,548 @@
+extern crate serde;
+extern crate rand;
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::time::{Duration as WallDuration, Instant};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::{Simulation},
+	control::Control,
+	inventory::Inventory,
+	genetic_plot::{plot, export, check_extension},
+	local_search::repair,
+	score::{Score, Scorer, BroScore, CacheStats, Values},
+	solver::{Production, Path, Step},
+	simulate::generate_path,
+	utils::{edit_distance, fibonacci_n, parallel_map, worker_count},
+};
+
+mod checkpoint;
+mod crossover;
+mod island;
+mod mutation;
+mod run_length;
+mod selection;
+mod termination;
+mod warm_start;
+
+pub use checkpoint::Checkpoint;
+pub use crossover::Crossover;
+pub use island::{Islands, Topology};
+pub use mutation::{Adaptation, Mutation, Operator};
+pub use run_length::{Encoding, RunLength, Gene, Genome, compress, expand};
+pub use selection::Selection;
+pub use termination::{Reason, Termination};
+pub use warm_start::{WarmStart, read_path};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	mutation_chance: f32,
+	max_depth: usize,
+	generation_size: usize,
+	iterations: usize,
+	// Stops before every iteration is done
+	termination: Termination,
+	time_weight: f32,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+	crossover: Crossover,
+	// Operators of the mutations and how their rates adapt
+	mutation: Mutation,
+	// Chance for a child to be bred from two parents instead of copied from one
+	crossover_rate: f32,
+	selection: Selection,
+	// Share of the generation kept as parents
+	parents_percentage: usize,
+	// Best paths always kept as parents, whatever the selection
+	elitism: usize,
+	// Threads generating and scoring the paths, 0 for one per core
+	workers: usize,
+	islands: Islands,
+	// File the run is saved to, to resume it later
+	checkpoint: Option<String>,
+	// Generations between checkpoints, 0 to only save at the end
+	checkpoint_interval: usize,
+	// Checkpoint to continue from
+	resume: Option<String>,
+	// Paths the first generation starts from
+	warm_start: WarmStart,
+	// Genes as steps, or as runs of a step for very long repetitive paths
+	encoding: Encoding,
+	run_length: RunLength,
+	// svg or png file the scores are plotted to
+	plot: Option<String>,
+	// csv or json file the stats are written to
+	stats: Option<String>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			mutation_chance: 0.01,
+			max_depth: 100,
+			generation_size: 100,
+			iterations: 10,
+			termination: Termination::default(),
+			time_weight: 0.1,
+			scorer: BroScore::default(),
+			values: Values::new(),
+			mutation: Mutation::default(),
+			crossover: Crossover::default(),
+			crossover_rate: 0.7,
+			selection: Selection::default(),
+			parents_percentage: 10,
+			elitism: 1,
+			workers: 0,
+			islands: Islands::default(),
+			checkpoint: None,
+			checkpoint_interval: 10,
+			resume: None,
+			warm_start: WarmStart::default(),
+			encoding: Encoding::default(),
+			run_length: RunLength::default(),
+			plot: None,
+			stats: None,
+		}
+	}
+}
+
+impl Config {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.mutation_chance.is_nan() || self.mutation_chance <= 0. || self.mutation_chance > 1. {
+			return Err(format!("mutation_chance must be in (0, 1], got {}", self.mutation_chance))
+		}
+		if !(0. ..=1.).contains(&self.crossover_rate) {
+			return Err(format!("crossover_rate must be in [0, 1], got {}", self.crossover_rate))
+		}
+		if self.generation_size == 0 {
+			return Err("generation_size must be greater than 0".to_string())
+		}
+		if self.max_depth == 0 {
+			return Err("max_depth must be greater than 0".to_string())
+		}
+		if self.iterations == 0 {
+			return Err("iterations must be greater than 0".to_string())
+		}
+		if self.time_weight.is_nan() || self.time_weight < 0. {
+			return Err(format!("time_weight must be positive, got {}", self.time_weight))
+		}
+		if !(1..=100).contains(&self.parents_percentage) {
+			return Err(format!("parents_percentage must be in [1, 100], got {}", self.parents_percentage))
+		}
+		if self.elitism > self.generation_size {
+			return Err(format!("elitism must be at most generation_size ({}), got {}", self.generation_size, self.elitism))
+		}
+		self.selection.validate()?;
+		self.mutation.validate()?;
+		self.termination.validate()?;
+		self.warm_start.validate()?;
+		self.run_length.validate()?;
+		if self.encoding == Encoding::RunLength && (self.islands.count() > 1 || self.checkpoint.is_some() || self.resume.is_some()) {
+			return Err("run_length genomes evolve a single population, without checkpoints".to_string())
+		}
+		if self.encoding == Encoding::RunLength && self.mutation != Mutation::default() {
+			return Err("run_length genomes mutate their runs with a fixed mutation_chance, mutation settings only apply to steps".to_string())
+		}
+		if let Some (path) = &self.plot {
+			check_extension(path, &["svg", "png"])?;
+		}
+		if let Some (path) = &self.stats {
+			check_extension(path, &["csv", "json"])?;
+		}
+		self.islands.validate()
+	}
+}
+
+// Columns of generation stats, as long as each other: a generation is pushed into all of them
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stats {
+	pub average_scores: Vec<Score>,
+	pub best_scores: Vec<Score>,
+	pub worst_scores: Vec<Score>,
+	// Standard deviation of the scores
+	pub score_deviations: Vec<f64>,
+	pub distinct_paths: Vec<usize>,
+	// Average edit distance between sampled pairs of paths
+	pub edit_distances: Vec<f64>,
+	// Share of the paths that couldn't be scored
+	pub infeasible_fractions: Vec<f64>,
+	pub evaluations_per_second: Vec<f64>,
+	pub best_paths: Vec<Path>,
+	// Chance of a path to be mutated in the generation, raised while the best path stagnates
+	pub mutation_chances: Vec<f64>,
+	// Why the run stopped, None for the stats of an island
+	pub termination: Option<Reason>,
+	// Stats of each island, empty with a single population
+	pub islands: Vec<Stats>,
+	// Only covers the last run when resumed
+	#[serde(skip)]
+	pub cache: CacheStats,
+}
+
+// Pairs of paths compared for the edit distance of a generation
+const DIVERSITY_PAIRS: usize = 100;
+
+// Stats of a single generation
+pub struct Generation {
+	pub average_score: Score,
+	pub best_score: Score,
+	pub worst_score: Score,
+	pub score_deviation: f64,
+	pub distinct_paths: usize,
+	pub edit_distance: f64,
+	pub infeasible_fraction: f64,
+	pub evaluations_per_second: f64,
+	pub best_path: Path,
+	pub mutation_chance: f64,
+}
+
+impl Generation {
+	// Arguments: sorted scores, the genomes in the same order, the best one as a path, how many
+	// couldn't be scored and the time spent scoring them. The seed draws the pairs compared for
+	// the edit distance
+	pub fn new<T: Eq + Hash>(scores: &[Score], genomes: &[&[T]], best_path: Path, infeasible: usize, elapsed: WallDuration, seed: u64, mutation_chance: f32) -> Self {
+		let mean = scores.iter().map(|score| { *score as f64 }).sum::<f64>() / scores.len().max(1) as f64;
+		let variance = scores.iter().map(|score| { (*score as f64 - mean).powi(2) }).sum::<f64>() / scores.len().max(1) as f64;
+		let distinct: HashSet<&[T]> = genomes.iter().cloned().collect();
+		let mut rng = StdRng::seed_from_u64(seed);
+		let distances: Vec<usize> = if genomes.len() < 2 {
+			vec![]
+		} else {
+			(0..DIVERSITY_PAIRS)
+				.map(|_| {
+					let (a, b) = (rng.gen_range(0, genomes.len()), rng.gen_range(0, genomes.len()));
+					edit_distance(genomes[a], genomes[b])
+				})
+				.collect()
+		};
+		let seconds = elapsed.as_secs_f64();
+		Self {
+			average_score: (scores.iter().map(|score| { *score as i64 }).sum::<i64>() / scores.len().max(1) as i64) as Score,
+			best_score: scores.first().cloned().unwrap_or(0),
+			worst_score: scores.last().cloned().unwrap_or(0),
+			score_deviation: variance.sqrt(),
+			distinct_paths: distinct.len(),
+			edit_distance: distances.iter().sum::<usize>() as f64 / distances.len().max(1) as f64,
+			infeasible_fraction: infeasible as f64 / genomes.len().max(1) as f64,
+			evaluations_per_second: if seconds > 0. { genomes.len() as f64 / seconds } else { 0. },
+			best_path,
+			mutation_chance: mutation_chance as f64,
+		}
+	}
+}
+
+impl Stats {
+	pub fn new() -> Self {
+		Self {
+			average_scores: vec![],
+			best_scores: vec![],
+			worst_scores: vec![],
+			score_deviations: vec![],
+			distinct_paths: vec![],
+			edit_distances: vec![],
+			infeasible_fractions: vec![],
+			evaluations_per_second: vec![],
+			best_paths: vec![],
+			mutation_chances: vec![],
+			termination: None,
+			islands: vec![],
+			cache: CacheStats::default(),
+		}
+	}
+
+	pub fn push(&mut self, generation: Generation) {
+		self.average_scores.push(generation.average_score);
+		self.best_scores.push(generation.best_score);
+		self.worst_scores.push(generation.worst_score);
+		self.score_deviations.push(generation.score_deviation);
+		self.distinct_paths.push(generation.distinct_paths);
+		self.edit_distances.push(generation.edit_distance);
+		self.infeasible_fractions.push(generation.infeasible_fraction);
+		self.evaluations_per_second.push(generation.evaluations_per_second);
+		self.best_paths.push(generation.best_path);
+		self.mutation_chances.push(generation.mutation_chance);
+	}
+
+	// Stats read from a checkpoint may have been edited
+	pub fn validate(&self) -> Result<(), String> {
+		let generations = self.best_scores.len();
+		let lengths = [
+			self.average_scores.len(),
+			self.worst_scores.len(),
+			self.score_deviations.len(),
+			self.distinct_paths.len(),
+			self.edit_distances.len(),
+			self.infeasible_fractions.len(),
+			self.evaluations_per_second.len(),
+			self.best_paths.len(),
+			self.mutation_chances.len(),
+		];
+		if lengths.iter().any(|length| { *length != generations }) {
+			return Err(format!("The stats of {} generations don't all have {} values", generations, generations))
+		}
+		self.islands.iter().try_for_each(Stats::validate)
+	}
+}
+
+struct GeneticSolver {
+	mutation_chance: f32,
+	mutation: Mutation,
+	adaptation: Adaptation,
+	// Mutations of the generation to credit once it's scored: position in the generation,
+	// operator index and the path before the mutation
+	credits: Vec<(usize, usize, Path)>,
+	// Sorted by name so seeded runs don't depend on the HashMap order
+	step_names: Vec<Step>,
+	crossover: Crossover,
+	crossover_rate: f32,
+	selection: Selection,
+	elitism: usize,
+	workers: usize,
+	max_depth: usize,
+	generation_size: usize,
+	parents_size: usize,
+	iterations: usize,
+	time_weight: f32,
+	bro_score: BroScore,
+	values: Values,
+	warm_start: WarmStart,
+	weigths: Vec<usize>,
+	simulation: Simulation,
+	stats: Stats,
+	parents: Vec<Path>,
+	best: Option<(Score, Path)>,
+	scorer: Scorer,
+	rng: StdRng,
+	// Seeds the generator at each generation, a checkpoint only has to save it
+	seed: u64,
+}
+
+// Solves with the plot and export asked by the config, and returns the stats for the caller to report
+pub fn solve<'a>(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<(Production, Stats), String> {
+	let (plot_path, stats_path) = (config.plot.clone(), config.stats.clone());
+	let (production, stats) = solve_with_stats(simulation, config, control, rng)?;
+	if let Some (reason) = stats.termination {
+		eprintln!("genetic: stopped after {} generations, {}", stats.best_scores.len(), reason);
+	}
+	if let Some (path) = stats_path {
+		export(&stats, &path)?;
+	}
+	// gnuplot may be missing, the production is still worth returning
+	if let Some (path) = plot_path {
+		if let Err (err) = plot(&stats, &path) {
+			eprintln!("Unable to plot: {}", err);
+		}
+	}
+	Ok((production, stats))
+}
+
+pub fn solve_with_stats(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<(Production, Stats), String> {
+	match config.encoding {
+		Encoding::Steps => island::solve(simulation, config, control, rng),
+		Encoding::RunLength => run_length::solve(simulation, config, control, rng),
+	}
+}
+
+impl GeneticSolver {
+	// TODO: broScore dyn
+	pub fn new(config: Config, simulation: Simulation, seed: u64) -> Self {
+		let parents_size = (config.generation_size * config.parents_percentage / 100).max(1);
+		let mut step_names: Vec<Step> = simulation.processes.keys().cloned().collect();
+		step_names.sort();
+		let mut solver = Self {
+			mutation_chance: config.mutation_chance,
+			adaptation: Adaptation::new(&config.mutation, config.mutation_chance),
+			credits: vec![],
+			mutation: config.mutation,
+			step_names,
+			crossover: config.crossover,
+			crossover_rate: config.crossover_rate,
+			selection: config.selection,
+			elitism: config.elitism.min(parents_size),
+			workers: worker_count(config.workers),
+			max_depth: config.max_depth,
+			generation_size: config.generation_size,
+			parents_size,
+			iterations: config.iterations,
+			time_weight: config.time_weight,
+			bro_score: config.scorer,
+			values: config.values.clone(),
+			warm_start: config.warm_start,
+			simulation: simulation.clone(),
+			weigths: fibonacci_n(config.generation_size),
+			stats: Stats::new(),
+			parents: vec![],
+			best: None,
+			scorer: Scorer::new(simulation, config.time_weight, config.scorer).with_values(config.values.clone()).with_horizon(config.max_depth),
+			rng: StdRng::seed_from_u64(seed),
+			seed,
+		};
+		solver.weigths.reverse();
+		solver
+	}
+
+	// One more generation, the first one is random
+	fn evolve(&mut self) -> Result<(), String> {
+		self.rng = StdRng::seed_from_u64(self.seed);
+		let generation = if self.stats.best_scores.is_empty() {
+			self.generate()?
+		} else {
+			let parents = std::mem::take(&mut self.parents);
+			let children = self.breed(parents);
+			self.shuffle(children)?
+		};
+		self.parents = self.select(generation);
+		self.seed = self.rng.gen();
+		Ok(())
+	}
+
+	// Migrants take the place of the worst parents
+	fn immigrate(&mut self, migrants: Vec<Path>) {
+		self.parents.truncate(self.parents.len().saturating_sub(migrants.len()));
+		self.parents.extend(migrants);
+	}
+
+	// Doable path mutated by an operator drawn from the config, and the operator's index
+	fn mutate(&mut self, mutation_force: f32, path: Path) -> Result<(usize, Path), String> {
+		let (index, operator) = self.adaptation.pick(&self.mutation, &mut self.rng);
+		let mutated_path = match operator {
+			Operator::Tail => self.regenerate_tail(mutation_force, path)?,
+			_ => {
+				let mut mutated_path = repair(&self.simulation, operator.apply(path, mutation_force, &self.step_names, &mut self.rng));
+				mutated_path.truncate(self.max_depth);
+				mutated_path
+			},
+		};
+		Ok((index, mutated_path))
+	}
+
+	fn regenerate_tail(&mut self, mutation_force: f32, mut path: Path) -> Result<Path, String> {
+		let len = path.len();
+		let split_at = len.saturating_sub((len as f32 * mutation_force) as usize + 1);
+		path.truncate(split_at);
+		let base_inventory = self.scorer.inventory(&path)?;
+		let rest = self.generate_one(len - split_at, &base_inventory);
+		let mutated_path = [&path[..], &rest[..]].concat();
+		Ok(mutated_path)
+	}
+
+	// Parents and their children, up to the generation size
+	fn breed(&mut self, parents: Vec<Path>) -> Vec<Path> {
+		let mut generation = parents.clone();
+		while !parents.is_empty() && generation.len() < self.generation_size {
+			let first = &parents[self.rng.gen_range(0, parents.len())];
+			let child = if self.rng.gen_range(0., 1.) < self.crossover_rate {
+				let second = &parents[self.rng.gen_range(0, parents.len())];
+				let mut child = repair(&self.simulation, self.crossover.apply(first, second, &mut self.rng));
+				child.truncate(self.max_depth);
+				child
+			} else {
+				first.clone()
+			};
+			generation.push(child);
+		}
+		generation
+	}
+
+	// Adaptive operators are credited in select, when the mutated path scores better than the original
+	fn shuffle(&mut self, steps: Vec<Path>) -> Result<Vec<Path>, String> {
+		let mutation_chance = self.adaptation.mutation_chance;
+		let mutation_mult = 1. / mutation_chance;
+		steps
+			.into_iter()
+			.enumerate()
+			.map(|(position, path)| {
+				let i = self.rng.gen_range(0., 1.);
+				if i <= mutation_chance {
+					let mutation_force = i * mutation_mult;
+					let original = if self.mutation.adaptive() { Some(path.clone()) } else { None };
+					let (index, mutated_path) = self.mutate(mutation_force, path)?;
+					if let Some (original) = original {
+						self.credits.push((position, index, original));
+					}
+					Ok(mutated_path)
+				} else {
+					Ok(path)
+				}
+			})
+			.collect()
+	}
+
+	fn generate_one(&mut self, len: usize, base_inventory: &Inventory) -> Path {
+		generate_path(&self.simulation, len, base_inventory, &mut self.rng)
+	}
+	
+	// First generation: doable random paths, then the warm start paths and their mutations.
+	// Each random path has its own seed so the generation doesn't depend on the workers
+	fn generate(&mut self) -> Result<Vec<Path>, String> {
+		let warm_paths = self.warm_start.paths(&self.simulation, self.max_depth, self.time_weight, self.bro_score, &self.values)?;
+		let seeded_count = if warm_paths.is_empty() { 0 } else { self.warm_start.seeded_count(self.generation_size) };
+		let seeds: Vec<u64> = (seeded_count..self.generation_size).map(|_| { self.rng.gen() }).collect();
+		let simulation = &self.simulation;
+		let max_depth = self.max_depth;
+		let mut generation = parallel_map(&seeds, self.workers, |seed| {
+			generate_path(simulation, max_depth, &simulation.inventory, &mut StdRng::seed_from_u64(*seed))
+		});
+		for i in 0..seeded_count {
+			let path = warm_paths[i % warm_paths.len()].clone();
+			let path = if i < warm_paths.len() {
+				path
+			} else {
+				let mutation_force = self.rng.gen_range(0., 1.);
+				self.mutate(mutation_force, path)?.1
+			};
+			generation.push(path);
+		}
+		Ok(generation)
+	}
+
+	// Parents of the next generation, best first
+	fn select(&mut self, paths: Vec<Path>) -> Vec<Path> {
+		let scorer = &self.scorer;
+		let start = Instant::now();
+		let results = parallel_map(&paths, self.workers, |path| { scorer.score(path) });
+		let elapsed = start.elapsed();
+		let infeasible = results.iter().filter(|result| { result.is_err() }).count();
+		let scores: Vec<Score> = results.into_iter().map(|result| { result.unwrap_or(-1) }).collect();
+		// Originals copied from a parent are read from the score cache
+		let credits = std::mem::take(&mut self.credits);
+		let originals: Vec<Path> = credits.iter().map(|(_, _, original)| { original.clone() }).collect();
+		let original_scores = parallel_map(&originals, self.workers, |path| { scorer.score(path).unwrap_or(-1) });
+		for ((position, index, _), original_score) in credits.into_iter().zip(original_scores) {
+			self.adaptation.record(index, scores[position] > original_score);
+		}
+		let mut p_scores: Vec<(Score, Path)> = scores.into_iter().zip(paths).collect();
+		p_scores.sort_by(|(score_a, _), (score_b, _)| { score_b.cmp(score_a) });
+		let scores: Vec<Score> = p_scores.iter().map(|p_score| { p_score.0 }).collect();
+		let sorted_paths: Vec<&[String]> = p_scores.iter().map(|(_, path)| { &path[..] }).collect();
+		let best_path = p_scores.first().map(|(_, path)| { path.clone() }).unwrap_or_default();
+		self.stats.push(Generation::new(&scores, &sorted_paths, best_path, infeasible, elapsed, self.seed, self.adaptation.mutation_chance));
+		let mut improved = false;
+		if let Some ((score, path)) = p_scores.first() {
+			if self.best.as_ref().is_none_or(|(best_score, _)| { score > best_score }) {
+				self.best = Some((*score, path.clone()));
+				improved = true;
+			}
+		}
+		self.adaptation.end_generation(&self.mutation, self.mutation_chance, improved);
+		// The others are selected among the rest of the generation
+		let elitism = self.elitism.min(p_scores.len());
+		let weights = &self.weigths[elitism.min(self.weigths.len())..];
+		let mut ranks: Vec<usize> = (0..elitism).collect();
+		ranks.extend(self.selection
+			.select(&scores[elitism..], weights, self.parents_size - elitism, &mut self.rng)
+			.into_iter()
+			.map(|rank| { rank + elitism }));
+		ranks
+			.into_iter()
+			.map(|rank| { p_scores[rank].1.clone() })
+			.collect()
+	}
+}
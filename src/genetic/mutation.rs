@@ -0,0 +1,283 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::solver::{Path, Step};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+	// Regenerates the tail of the path, as long as the mutation force
+	Tail,
+	// Point mutations, on a single step
+	Replace,
+	Insert,
+	Delete,
+	Swap,
+	// Moves a block of steps elsewhere in the path, as long as the mutation force
+	BlockMove,
+	// Repeats a block of steps right after itself, as long as the mutation force
+	DuplicateBlock,
+}
+
+const OPERATORS: [Operator; 7] = [
+	Operator::Tail,
+	Operator::Replace,
+	Operator::Insert,
+	Operator::Delete,
+	Operator::Swap,
+	Operator::BlockMove,
+	Operator::DuplicateBlock,
+];
+
+// Ordered pair of distinct cuts in 0..=len, at most `max` apart
+fn block<R: Rng>(len: usize, max: usize, rng: &mut R) -> (usize, usize) {
+	let size = rng.gen_range(1, max.clamp(1, len) + 1);
+	let start = rng.gen_range(0, len - size + 1);
+	(start, start + size)
+}
+
+impl Operator {
+	// The mutated path may not be doable, it has to be repaired. The tail is left to the caller,
+	// which knows how to generate doable steps
+	pub fn apply<R: Rng>(&self, mut path: Path, force: f32, steps: &[Step], rng: &mut R) -> Path {
+		let len = path.len();
+		let block_size = (len as f32 * force) as usize + 1;
+		match self {
+			Operator::Insert if !steps.is_empty() => {
+				let i = rng.gen_range(0, len + 1);
+				path.insert(i, steps[rng.gen_range(0, steps.len())].clone());
+			},
+			_ if len == 0 => (),
+			Operator::Replace if !steps.is_empty() => {
+				let i = rng.gen_range(0, len);
+				path[i] = steps[rng.gen_range(0, steps.len())].clone();
+			},
+			Operator::Delete => { path.remove(rng.gen_range(0, len)); },
+			Operator::Swap if len > 1 => {
+				let i = rng.gen_range(0, len);
+				let j = (i + rng.gen_range(1, len)) % len;
+				path.swap(i, j);
+			},
+			Operator::BlockMove => {
+				let (start, end) = block(len, block_size, rng);
+				let moved: Path = path.drain(start..end).collect();
+				let at = rng.gen_range(0, path.len() + 1);
+				path.splice(at..at, moved);
+			},
+			Operator::DuplicateBlock => {
+				let (start, end) = block(len, block_size, rng);
+				let copy = path[start..end].to_vec();
+				path.splice(end..end, copy);
+			},
+			_ => (),
+		}
+		path
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Mutation {
+	// Operators a mutation is drawn from
+	operators: Vec<Operator>,
+	// Operators drawn according to their recent success instead of uniformly
+	adaptive: bool,
+	// Weight of the last generation in the success rate of an operator
+	adaptation_rate: f32,
+	// Lowest probability of an adaptive operator, so none is given up for good
+	min_probability: f32,
+	// Generations without a better path before mutation_chance is raised, 0 to keep it fixed
+	stagnation: usize,
+	// mutation_chance is multiplied by it at each generation past `stagnation`, and reset on improvement
+	stagnation_factor: f32,
+	max_mutation_chance: f32,
+}
+
+impl Default for Mutation {
+	fn default() -> Self {
+		Self {
+			operators: OPERATORS.to_vec(),
+			adaptive: true,
+			adaptation_rate: 0.3,
+			min_probability: 0.05,
+			stagnation: 5,
+			stagnation_factor: 1.5,
+			max_mutation_chance: 0.5,
+		}
+	}
+}
+
+impl Mutation {
+	pub fn adaptive(&self) -> bool {
+		self.adaptive
+	}
+
+	pub fn validate(&self) -> Result<(), String> {
+		if self.operators.is_empty() {
+			return Err("mutation.operators can't be empty".to_string())
+		}
+		if self.adaptation_rate.is_nan() || self.adaptation_rate <= 0. || self.adaptation_rate > 1. {
+			return Err(format!("mutation.adaptation_rate must be in (0, 1], got {}", self.adaptation_rate))
+		}
+		if self.min_probability.is_nan() || self.min_probability < 0. || self.min_probability * self.operators.len() as f32 > 1. {
+			return Err(format!("mutation.min_probability must be in [0, 1 / {}], got {}", self.operators.len(), self.min_probability))
+		}
+		if self.stagnation_factor.is_nan() || self.stagnation_factor < 1. {
+			return Err(format!("mutation.stagnation_factor must be at least 1, got {}", self.stagnation_factor))
+		}
+		if self.max_mutation_chance.is_nan() || self.max_mutation_chance <= 0. || self.max_mutation_chance > 1. {
+			return Err(format!("mutation.max_mutation_chance must be in (0, 1], got {}", self.max_mutation_chance))
+		}
+		Ok(())
+	}
+}
+
+// Mutation state of a population, saved in checkpoints
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Adaptation {
+	// Raised while the best path stagnates
+	pub mutation_chance: f32,
+	// Smoothed success rate of each operator, in the config's order
+	qualities: Vec<f64>,
+	stagnant_generations: usize,
+	// Mutations and improvements of each operator in the current generation
+	#[serde(skip)]
+	uses: Vec<usize>,
+	#[serde(skip)]
+	successes: Vec<usize>,
+}
+
+impl Adaptation {
+	pub fn new(mutation: &Mutation, mutation_chance: f32) -> Self {
+		let count = mutation.operators.len();
+		Self {
+			mutation_chance,
+			// Optimistic, every operator gets tried before its rate drops
+			qualities: vec![1.; count],
+			stagnant_generations: 0,
+			uses: vec![0; count],
+			successes: vec![0; count],
+		}
+	}
+
+	// A saved adaptation only fits the operators it was made for
+	pub fn validate(&self, mutation: &Mutation) -> Result<(), String> {
+		if self.qualities.len() != mutation.operators.len() {
+			return Err(format!("The checkpoint adapts {} mutation operators, the config has {}", self.qualities.len(), mutation.operators.len()))
+		}
+		Ok(())
+	}
+
+	pub fn probabilities(&self, mutation: &Mutation) -> Vec<f64> {
+		let count = mutation.operators.len();
+		let total: f64 = self.qualities.iter().sum();
+		if !mutation.adaptive || total <= 0. {
+			return vec![1. / count as f64; count]
+		}
+		let min = mutation.min_probability as f64;
+		self.qualities
+			.iter()
+			.map(|quality| { min + (1. - min * count as f64) * quality / total })
+			.collect()
+	}
+
+	// Index of the operator in the config, and the operator
+	pub fn pick<R: Rng>(&self, mutation: &Mutation, rng: &mut R) -> (usize, Operator) {
+		let probabilities = self.probabilities(mutation);
+		let mut drawn = rng.gen_range(0., probabilities.iter().sum::<f64>());
+		let index = probabilities
+			.iter()
+			.position(|probability| {
+				drawn -= probability;
+				drawn < 0.
+			})
+			.unwrap_or(probabilities.len() - 1);
+		(index, mutation.operators[index])
+	}
+
+	// A mutation made by the operator, better than the path it was made on or not
+	pub fn record(&mut self, index: usize, improved: bool) {
+		self.uses.resize(self.qualities.len(), 0);
+		self.successes.resize(self.qualities.len(), 0);
+		self.uses[index] += 1;
+		if improved {
+			self.successes[index] += 1;
+		}
+	}
+
+	// Updates the operators' rates from the generation's mutations, and the mutation chance
+	// from whether the generation found a better path
+	pub fn end_generation(&mut self, mutation: &Mutation, base_chance: f32, improved: bool) {
+		let rate = mutation.adaptation_rate as f64;
+		for (i, quality) in self.qualities.iter_mut().enumerate() {
+			let uses = self.uses.get(i).cloned().unwrap_or(0);
+			if uses > 0 {
+				let success = self.successes[i] as f64 / uses as f64;
+				*quality = (1. - rate) * *quality + rate * success;
+			}
+		}
+		self.uses.clear();
+		self.successes.clear();
+		if improved {
+			self.stagnant_generations = 0;
+			self.mutation_chance = base_chance;
+		} else {
+			self.stagnant_generations += 1;
+			if mutation.stagnation > 0 && self.stagnant_generations >= mutation.stagnation {
+				let max = mutation.max_mutation_chance.max(base_chance);
+				self.mutation_chance = (self.mutation_chance * mutation.stagnation_factor).min(max);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use super::{Adaptation, Mutation, Operator};
+
+	#[test]
+	fn test_adaptation() {
+		let mutation = Mutation::default();
+		let mut adaptation = Adaptation::new(&mutation, 0.1);
+		// Only swaps improve the paths
+		for index in 0..mutation.operators.len() {
+			adaptation.record(index, mutation.operators[index] == Operator::Swap);
+		}
+		adaptation.end_generation(&mutation, 0.1, false);
+		let probabilities = adaptation.probabilities(&mutation);
+		let swap = mutation.operators.iter().position(|operator| { *operator == Operator::Swap }).unwrap();
+		assert!((probabilities.iter().sum::<f64>() - 1.).abs() < 1e-9);
+		assert!(probabilities.iter().enumerate().all(|(i, probability)| { i == swap || *probability < probabilities[swap] }));
+		assert!(probabilities.iter().all(|probability| { *probability >= 0.05 }));
+		// The chance is raised after `stagnation` generations without improvement, and reset on one
+		for _ in 1..mutation.stagnation {
+			assert_eq!(adaptation.mutation_chance, 0.1);
+			adaptation.end_generation(&mutation, 0.1, false);
+		}
+		assert!((adaptation.mutation_chance - 0.15).abs() < 1e-6);
+		adaptation.end_generation(&mutation, 0.1, true);
+		assert_eq!(adaptation.mutation_chance, 0.1);
+		// Restored with other operators, it would credit the wrong ones
+		assert!(adaptation.validate(&mutation).is_ok());
+		let fewer = Mutation { operators: vec![Operator::Tail, Operator::Swap], ..Mutation::default() };
+		assert!(adaptation.validate(&fewer).is_err());
+	}
+
+	#[test]
+	fn test_block_operators_keep_the_steps() {
+		let mut rng = StdRng::seed_from_u64(0);
+		let path: Vec<String> = (0..10).map(|i| { i.to_string() }).collect();
+		let mut moved = Operator::BlockMove.apply(path.clone(), 0.5, &[], &mut rng);
+		moved.sort();
+		let mut sorted = path.clone();
+		sorted.sort();
+		assert_eq!(moved, sorted);
+		let duplicated = Operator::DuplicateBlock.apply(path.clone(), 0.5, &[], &mut rng);
+		assert!(duplicated.len() > path.len() && duplicated.len() <= path.len() + 6);
+		assert!(duplicated.iter().all(|step| { path.contains(step) }));
+		assert_eq!(Operator::Replace.apply(vec![], 0.5, &["a".to_string()], &mut rng), Vec::<String>::new());
+		assert_eq!(Operator::Insert.apply(vec![], 0.5, &["a".to_string()], &mut rng), vec!["a".to_string()]);
+	}
+}
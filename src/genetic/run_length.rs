@@ -0,0 +1,432 @@
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::{Simulation, Process},
+	control::Control,
+	inventory::Inventory,
+	score::{Score, Scorer},
+	solver::{Production, Path, Step, batchify},
+	simulate::{max_repeats, repeat_process},
+	utils::{fibonacci_n, parallel_map, worker_count},
+};
+use super::{Config, Crossover, Generation, Reason, Selection, Stats};
+
+// A process run `count` times in a row
+pub type Gene = (Step, usize);
+pub type Genome = Vec<Gene>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+	// One gene per step
+	#[default]
+	Steps,
+	// One gene per run of the same step, max_depth bounds the genes. Runs have their own
+	// mutations, the mutation settings are left to steps
+	RunLength,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RunLength {
+	// Highest count of a gene
+	max_repeat: usize,
+	// Highest number of steps once the genome is expanded
+	max_steps: usize,
+}
+
+impl Default for RunLength {
+	fn default() -> Self {
+		Self {
+			max_repeat: 10_000,
+			max_steps: 100_000,
+		}
+	}
+}
+
+impl RunLength {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.max_repeat == 0 {
+			return Err("run_length.max_repeat must be greater than 0".to_string())
+		}
+		if self.max_steps == 0 {
+			return Err("run_length.max_steps must be greater than 0".to_string())
+		}
+		Ok(())
+	}
+}
+
+pub fn expand(genome: &[Gene]) -> Path {
+	genome
+		.iter()
+		.flat_map(|(step, count)| { std::iter::repeat_n(step.clone(), *count) })
+		.collect()
+}
+
+// Runs of the same step merged in genes
+pub fn compress(path: &[Step]) -> Genome {
+	let mut genome: Genome = vec![];
+	for step in path {
+		match genome.last_mut() {
+			Some ((last, count)) if last == step => *count += 1,
+			_ => genome.push((step.clone(), 1)),
+		}
+	}
+	genome
+}
+
+// Genes as `step*count`, to show a genome as a path
+fn describe(genome: &[Gene]) -> Path {
+	genome.iter().map(|(step, count)| { format!("{}*{}", step, count) }).collect()
+}
+
+struct RunLengthSolver {
+	mutation_chance: f32,
+	crossover: Crossover,
+	crossover_rate: f32,
+	selection: Selection,
+	elitism: usize,
+	workers: usize,
+	max_genes: usize,
+	max_repeat: usize,
+	max_steps: usize,
+	generation_size: usize,
+	parents_size: usize,
+	iterations: usize,
+	weigths: Vec<usize>,
+	// Sorted by name so seeded runs don't depend on the HashMap order
+	processes: Vec<Process>,
+	simulation: Simulation,
+	scorer: Scorer,
+	stats: Stats,
+	parents: Vec<Genome>,
+	best: Option<(Score, Genome)>,
+	rng: StdRng,
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<(Production, Stats), String> {
+	let warm_paths = config.warm_start.paths(&simulation, usize::MAX, config.time_weight, config.scorer, &config.values)?;
+	let termination = config.termination.clone();
+	let mut solver = RunLengthSolver::new(config, simulation.clone(), rng.gen());
+	let start = Instant::now();
+	let mut stagnant_generations = 0;
+	let mut reason = Reason::Iterations;
+	for generation in 0..solver.iterations {
+		if control.is_cancelled() {
+			reason = Reason::Cancelled;
+			break
+		}
+		let genomes = if generation == 0 {
+			solver.generate(&warm_paths)
+		} else {
+			let parents = std::mem::take(&mut solver.parents);
+			let children = solver.breed(parents);
+			solver.shuffle(children)
+		};
+		let previous = solver.best.as_ref().map(|(score, _)| { *score });
+		solver.parents = solver.select(genomes);
+		stagnant_generations += 1;
+		let best_score = solver.best.as_ref().map(|(score, _)| { *score });
+		if let Some ((score, genome)) = &solver.best {
+			if previous.is_none_or(|previous| { *score > previous }) {
+				control.incumbent(&expand(genome));
+				stagnant_generations = 0;
+			}
+		}
+		if let Some (stop) = termination.reason(best_score, stagnant_generations, start.elapsed()) {
+			reason = stop;
+			break
+		}
+	}
+	solver.stats.termination = Some(reason);
+	let best = solver.best.map(|(_, genome)| { genome }).unwrap_or_default();
+	Ok((batchify(&simulation, expand(&best))?, solver.stats))
+}
+
+impl RunLengthSolver {
+	fn new(config: Config, simulation: Simulation, seed: u64) -> Self {
+		let parents_size = (config.generation_size * config.parents_percentage / 100).max(1);
+		let mut processes: Vec<Process> = simulation.processes.values().cloned().collect();
+		processes.sort_by(|a, b| { a.name.cmp(&b.name) });
+		let mut weigths = fibonacci_n(config.generation_size);
+		weigths.reverse();
+		Self {
+			mutation_chance: config.mutation_chance,
+			crossover: config.crossover,
+			crossover_rate: config.crossover_rate,
+			selection: config.selection,
+			elitism: config.elitism.min(parents_size),
+			workers: worker_count(config.workers),
+			max_genes: config.max_depth,
+			max_repeat: config.run_length.max_repeat,
+			max_steps: config.run_length.max_steps,
+			generation_size: config.generation_size,
+			parents_size,
+			iterations: config.iterations,
+			weigths,
+			processes,
+			scorer: Scorer::new(simulation.clone(), config.time_weight, config.scorer).with_values(config.values.clone()).with_horizon(config.max_depth),
+			simulation,
+			stats: Stats::new(),
+			parents: vec![],
+			best: None,
+			rng: StdRng::seed_from_u64(seed),
+		}
+	}
+
+	// Genes cut to what can be done, runs of the same step merged, with the inventory at the end
+	fn clamp(&self, genome: Genome) -> (Genome, Inventory) {
+		let mut inventory = self.simulation.inventory.clone();
+		let mut clamped: Genome = vec![];
+		let mut steps = 0;
+		for (step, count) in genome {
+			let process = match self.simulation.processes.get(&step) {
+				Some (process) => process,
+				None => continue,
+			};
+			let count = count
+				.min(max_repeats(process, &inventory).unwrap_or(usize::MAX))
+				.min(self.max_repeat)
+				.min(self.max_steps - steps);
+			if count == 0 {
+				continue
+			}
+			inventory = repeat_process(inventory, process, count);
+			steps += count;
+			match clamped.last_mut() {
+				Some ((last, last_count)) if *last == step => *last_count += count,
+				_ => clamped.push((step, count)),
+			}
+		}
+		(clamped, inventory)
+	}
+
+	// Counts are drawn on a log scale, long runs are as likely as short ones
+	fn random_gene(&mut self, inventory: &Inventory, steps: usize) -> Option<Gene> {
+		let limit = self.max_repeat.min(self.max_steps.saturating_sub(steps));
+		let available: Vec<(&Process, usize)> = self.processes
+			.iter()
+			.map(|process| { (process, max_repeats(process, inventory).unwrap_or(usize::MAX).min(limit)) })
+			.filter(|(_, max)| { *max > 0 })
+			.collect();
+		if available.is_empty() {
+			return None
+		}
+		let (process, max) = available[self.rng.gen_range(0, available.len())];
+		let count = if max == 1 { 1 } else { self.rng.gen_range(0., (max as f64).ln()).exp() as usize };
+		Some((process.name.clone(), count.clamp(1, max)))
+	}
+
+	// First generation: the warm start paths, the rest random
+	fn generate(&mut self, warm_paths: &[Path]) -> Vec<Genome> {
+		let mut generation: Vec<Genome> = warm_paths.iter().map(|path| { self.clamp(compress(path)).0 }).collect();
+		generation.truncate(self.generation_size);
+		while generation.len() < self.generation_size {
+			let genome = self.generate_one();
+			generation.push(genome);
+		}
+		generation
+	}
+
+	// Random doable genome of at most max_genes genes
+	fn generate_one(&mut self) -> Genome {
+		let mut genome: Genome = vec![];
+		let mut inventory = self.simulation.inventory.clone();
+		let mut steps = 0;
+		for _ in 0..self.max_genes {
+			let (step, count) = match self.random_gene(&inventory, steps) {
+				Some (gene) => gene,
+				None => break,
+			};
+			if let Some (process) = self.simulation.processes.get(&step) {
+				inventory = repeat_process(inventory, process, count);
+			}
+			steps += count;
+			genome.push((step, count));
+		}
+		genome
+	}
+
+	// Scales a count, replaces, inserts or removes a gene
+	fn mutate(&mut self, mut genome: Genome) -> Genome {
+		let i = self.rng.gen_range(0, genome.len() + 1);
+		let operation = if genome.is_empty() { 2 } else { self.rng.gen_range(0, 4) };
+		let (inventory, steps) = {
+			let (prefix, inventory) = self.clamp(genome[..i.min(genome.len())].to_vec());
+			(inventory, prefix.iter().map(|(_, count)| { count }).sum::<usize>())
+		};
+		let i = i.min(genome.len().saturating_sub(1));
+		match operation {
+			0 => {
+				let factor = self.rng.gen_range(-(4f64.ln()), 4f64.ln()).exp();
+				let count = genome[i].1;
+				let scaled = ((count as f64 * factor).round() as usize).max(1);
+				genome[i].1 = if scaled == count { count + 1 } else { scaled };
+			},
+			1 => {
+				if let Some (gene) = self.random_gene(&inventory, steps) {
+					genome[i] = gene;
+				}
+			},
+			2 => {
+				if let Some (gene) = self.random_gene(&inventory, steps) {
+					genome.insert(i.min(genome.len()), gene);
+				}
+			},
+			_ => {
+				genome.remove(i);
+			},
+		}
+		let (mut genome, _) = self.clamp(genome);
+		genome.truncate(self.max_genes);
+		genome
+	}
+
+	// Parents and their children, up to the generation size
+	fn breed(&mut self, parents: Vec<Genome>) -> Vec<Genome> {
+		let mut generation = parents.clone();
+		while !parents.is_empty() && generation.len() < self.generation_size {
+			let first = &parents[self.rng.gen_range(0, parents.len())];
+			let child = if self.rng.gen_range(0., 1.) < self.crossover_rate {
+				let second = &parents[self.rng.gen_range(0, parents.len())];
+				let child = self.crossover.apply(first, second, &mut self.rng);
+				let (mut child, _) = self.clamp(child);
+				child.truncate(self.max_genes);
+				child
+			} else {
+				first.clone()
+			};
+			generation.push(child);
+		}
+		generation
+	}
+
+	fn shuffle(&mut self, genomes: Vec<Genome>) -> Vec<Genome> {
+		genomes
+			.into_iter()
+			.map(|genome| {
+				if self.rng.gen_range(0., 1.) < self.mutation_chance {
+					self.mutate(genome)
+				} else {
+					genome
+				}
+			})
+			.collect()
+	}
+
+	// Parents of the next generation, best first
+	fn select(&mut self, genomes: Vec<Genome>) -> Vec<Genome> {
+		let start = Instant::now();
+		let solver = &*self;
+		let results = parallel_map(&genomes, self.workers, |genome| {
+			let (_, inventory) = solver.clamp(genome.clone());
+			solver.scorer.score_inventory(inventory)
+		});
+		let elapsed = start.elapsed();
+		let infeasible = results.iter().filter(|result| { result.is_err() }).count();
+		let mut g_scores: Vec<(Score, Genome)> = results
+			.into_iter()
+			.map(|result| { result.unwrap_or(-1) })
+			.zip(genomes)
+			.collect();
+		g_scores.sort_by(|(score_a, _), (score_b, _)| { score_b.cmp(score_a) });
+		let scores: Vec<Score> = g_scores.iter().map(|g_score| { g_score.0 }).collect();
+		let sorted: Vec<&[Gene]> = g_scores.iter().map(|(_, genome)| { &genome[..] }).collect();
+		let best_path = g_scores.first().map(|(_, genome)| { describe(genome) }).unwrap_or_default();
+		let seed = self.rng.gen();
+		self.stats.push(Generation::new(&scores, &sorted, best_path, infeasible, elapsed, seed, self.mutation_chance));
+		if let Some ((score, genome)) = g_scores.first() {
+			if self.best.as_ref().is_none_or(|(best_score, _)| { score > best_score }) {
+				self.best = Some((*score, genome.clone()));
+			}
+		}
+		let elitism = self.elitism.min(g_scores.len());
+		let weights = &self.weigths[elitism.min(self.weigths.len())..];
+		let mut ranks: Vec<usize> = (0..elitism).collect();
+		ranks.extend(self.selection
+			.select(&scores[elitism..], weights, self.parents_size - elitism, &mut self.rng)
+			.into_iter()
+			.map(|rank| { rank + elitism }));
+		ranks
+			.into_iter()
+			.map(|rank| { g_scores[rank].1.clone() })
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::{ast::parse, control::Control, genetic::solve_with_stats, simulate::simulate, solver::flatten};
+	use super::{Config, RunLengthSolver, compress, expand, solve};
+
+	#[test]
+	fn test_clamp_matches_expanded_simulation() {
+		let simulation = parse(read_to_string("ressources/inception").unwrap()).unwrap();
+		let solver = RunLengthSolver::new(Config::default(), simulation.clone(), 0);
+		let genome = vec![
+			("make_sec".to_string(), 150),
+			("make_minute".to_string(), 5),
+			("make_sec".to_string(), 10),
+			("start_dream".to_string(), 3),
+			("make_sec".to_string(), 10),
+		];
+		let (clamped, inventory) = solver.clamp(genome);
+
+		// 150 seconds make 2 minutes, starting a dream takes the clock back
+		assert_eq!(clamped, vec![
+			("make_sec".to_string(), 150),
+			("make_minute".to_string(), 2),
+			("make_sec".to_string(), 10),
+			("start_dream".to_string(), 1),
+		]);
+		assert_eq!(simulate(&simulation, &expand(&clamped), false).unwrap().0, inventory);
+		assert_eq!(compress(&expand(&clamped)), clamped);
+	}
+
+	#[test]
+	fn test_run_length_goes_past_max_depth() {
+		let simulation = parse(read_to_string("ressources/inception").unwrap()).unwrap();
+		let config: Config = serde_json::from_str(r#"{
+			"encoding": "run_length", "max_depth": 10, "generation_size": 20, "iterations": 5, "mutation_chance": 0.3
+		}"#).unwrap();
+		let (production, stats) = solve(simulation.clone(), config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+		let steps: usize = production.iter().map(|(_, path)| { path.len() }).sum();
+
+		assert!(steps > 10);
+		assert_eq!(stats.best_scores.len(), 5);
+	}
+
+	#[test]
+	fn test_run_length_rejects_mutation_settings() {
+		let config = |mutation: &str| -> Config {
+			serde_json::from_str(&format!(r#"{{ "encoding": "run_length", "mutation": {} }}"#, mutation)).unwrap()
+		};
+
+		assert!(config("{}").validate().is_ok());
+		assert!(config(r#"{ "adaptive": false }"#).validate().is_err());
+		assert!(config(r#"{ "operators": ["swap"] }"#).validate().is_err());
+	}
+
+	#[test]
+	fn test_run_length_reaches_goals_out_of_steps_reach() {
+		// A house takes 51 steps, more than max_depth
+		let simulation = parse("hache:1\ncouper:(hache:1):(hache:1;bois:1):1\nconstruire:(bois:50):(maison:1):10\noptimize:(maison)\n".to_string()).unwrap();
+		let houses = |encoding: &str| {
+			let config: Config = serde_json::from_str(&format!(r#"{{
+				"encoding": "{}", "max_depth": 10, "generation_size": 20, "iterations": 5, "mutation_chance": 0.3
+			}}"#, encoding)).unwrap();
+			let (production, _) = solve_with_stats(simulation.clone(), config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+			simulate(&simulation, &flatten(&production), false).unwrap().0.get("maison").copied().unwrap_or(0)
+		};
+
+		assert_eq!(houses("steps"), 0);
+		assert!(houses("run_length") > 0);
+	}
+}
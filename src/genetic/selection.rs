@@ -0,0 +1,116 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::score::Score;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum Selection {
+	// The best ones
+	#[default]
+	Truncation,
+	// Drawn with the fibonacci weight of their rank
+	Roulette,
+	// Best of `size` random ones
+	Tournament { size: usize },
+	// Drawn with weight exp(normalized score / temperature), a low temperature favours the best
+	Boltzmann { temperature: f64 },
+}
+
+fn draw<R: Rng>(weights: &[f64], rng: &mut R) -> usize {
+	let total: f64 = weights.iter().sum();
+	if total <= 0. {
+		return rng.gen_range(0, weights.len())
+	}
+	let mut target = rng.gen_range(0., total);
+	for (i, weight) in weights.iter().enumerate() {
+		if target < *weight {
+			return i
+		}
+		target -= weight;
+	}
+	weights.len() - 1
+}
+
+impl Selection {
+	pub fn validate(&self) -> Result<(), String> {
+		match self {
+			Selection::Tournament { size: 0 } => Err("selection.size must be greater than 0".to_string()),
+			Selection::Boltzmann { temperature } if temperature.is_nan() || *temperature <= 0. => {
+				Err(format!("selection.temperature must be positive, got {}", temperature))
+			},
+			_ => Ok(()),
+		}
+	}
+
+	// Ranks of `count` parents among `scores`, sorted best first. `weights` are the
+	// fibonacci weights of the ranks, the best rank first
+	pub fn select<R: Rng>(&self, scores: &[Score], weights: &[usize], count: usize, rng: &mut R) -> Vec<usize> {
+		if scores.is_empty() {
+			return vec![]
+		}
+		let mut ranks: Vec<usize> = match self {
+			Selection::Truncation => (0..count.min(scores.len())).collect(),
+			Selection::Roulette => {
+				let weights: Vec<f64> = (0..scores.len())
+					.map(|rank| { *weights.get(rank).unwrap_or(&0) as f64 })
+					.collect();
+				(0..count).map(|_| { draw(&weights, rng) }).collect()
+			},
+			Selection::Tournament { size } => {
+				(0..count)
+					.map(|_| {
+						(0..*size)
+							.map(|_| { rng.gen_range(0, scores.len()) })
+							.min()
+							.unwrap_or(0)
+					})
+					.collect()
+			},
+			Selection::Boltzmann { temperature } => {
+				let best = scores[0] as f64;
+				let range = (best - scores[scores.len() - 1] as f64).max(1.);
+				let weights: Vec<f64> = scores
+					.iter()
+					.map(|score| { ((*score as f64 - best) / range / temperature).exp() })
+					.collect();
+				(0..count).map(|_| { draw(&weights, rng) }).collect()
+			},
+		};
+		ranks.sort_unstable();
+		ranks
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use super::Selection;
+
+	#[test]
+	fn test_truncation_takes_the_best() {
+		let mut rng = StdRng::seed_from_u64(0);
+		let ranks = Selection::Truncation.select(&[50, 40, 30, 20], &[2, 1, 1], 2, &mut rng);
+
+		assert_eq!(ranks, vec![0, 1]);
+	}
+
+	#[test]
+	fn test_roulette_ignores_unweighted_ranks() {
+		let mut rng = StdRng::seed_from_u64(0);
+		let ranks = Selection::Roulette.select(&[50, 40, 30, 20], &[2, 1], 50, &mut rng);
+
+		assert_eq!(ranks.len(), 50);
+		assert!(ranks.iter().all(|rank| { *rank < 2 }));
+	}
+
+	#[test]
+	fn test_cold_boltzmann_takes_the_best() {
+		let mut rng = StdRng::seed_from_u64(0);
+		let selection = Selection::Boltzmann { temperature: 0.001 };
+		let ranks = selection.select(&[50, 40, 30, 20], &[], 10, &mut rng);
+
+		assert_eq!(ranks, vec![0; 10]);
+	}
+}
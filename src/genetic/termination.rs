@@ -0,0 +1,94 @@
+use std::fmt;
+use std::time::Duration as WallDuration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::score::Score;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Termination {
+	// Generations without a better path before stopping, 0 to run every iteration
+	stagnation: usize,
+	// Score good enough to stop at
+	target: Option<Score>,
+	// Best score known to be reachable, nothing better is searched once it's matched
+	upper_bound: Option<Score>,
+	// Milliseconds, 0 for no limit. Restarts when resumed
+	time_limit: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+	Iterations,
+	Stagnation,
+	Target,
+	UpperBound,
+	TimeLimit,
+	// By the caller, or the run's time limit
+	Cancelled,
+}
+
+impl fmt::Display for Reason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let reason = match self {
+			Reason::Iterations => "every iteration done",
+			Reason::Stagnation => "no better path for termination.stagnation generations",
+			Reason::Target => "termination.target reached",
+			Reason::UpperBound => "termination.upper_bound matched",
+			Reason::TimeLimit => "termination.time_limit reached",
+			Reason::Cancelled => "cancelled",
+		};
+		write!(f, "{}", reason)
+	}
+}
+
+impl Termination {
+	pub fn validate(&self) -> Result<(), String> {
+		if let (Some (target), Some (upper_bound)) = (self.target, self.upper_bound) {
+			if target > upper_bound {
+				return Err(format!("termination.target ({}) can't be above termination.upper_bound ({})", target, upper_bound))
+			}
+		}
+		Ok(())
+	}
+
+	// Reason to stop after a generation, from the best score so far, the generations since
+	// it was found and the time spent
+	pub fn reason(&self, best: Option<Score>, stagnant_generations: usize, elapsed: WallDuration) -> Option<Reason> {
+		let reached = |limit: Option<Score>| { matches!((best, limit), (Some (best), Some (limit)) if best >= limit) };
+		if reached(self.upper_bound) {
+			Some(Reason::UpperBound)
+		} else if reached(self.target) {
+			Some(Reason::Target)
+		} else if self.stagnation > 0 && stagnant_generations >= self.stagnation {
+			Some(Reason::Stagnation)
+		} else if self.time_limit > 0 && elapsed >= WallDuration::from_millis(self.time_limit) {
+			Some(Reason::TimeLimit)
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::{Reason, Termination};
+
+	#[test]
+	fn test_reason() {
+		let termination = Termination { stagnation: 3, target: Some(50), upper_bound: Some(60), time_limit: 1000 };
+		let second = Duration::from_secs(1);
+		assert_eq!(termination.reason(None, 0, Duration::ZERO), None);
+		assert_eq!(termination.reason(Some(49), 2, Duration::ZERO), None);
+		assert_eq!(termination.reason(Some(49), 3, Duration::ZERO), Some(Reason::Stagnation));
+		assert_eq!(termination.reason(Some(49), 0, second), Some(Reason::TimeLimit));
+		assert_eq!(termination.reason(Some(50), 3, second), Some(Reason::Target));
+		assert_eq!(termination.reason(Some(60), 0, Duration::ZERO), Some(Reason::UpperBound));
+		assert_eq!(Termination::default().reason(Some(1000), 1000, second * 1000), None);
+		assert!(Termination { target: Some(61), ..termination }.validate().is_err());
+	}
+}
@@ -0,0 +1,132 @@
+use std::fs::read_to_string;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	beam::{solve as beam_solve, Config as BeamConfig},
+	control::Control,
+	local_search::repair,
+	score::{BroScore, Values},
+	solver::{Production, Path, flatten},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WarmStart {
+	// Saved productions or traces, see read_path
+	files: Vec<String>,
+	// Adds the greedy solver's production
+	greedy: bool,
+	// Share of the first generation left random, the rest are the saved paths and their mutations
+	random_fraction: f32,
+}
+
+impl Default for WarmStart {
+	fn default() -> Self {
+		Self {
+			files: vec![],
+			greedy: false,
+			random_fraction: 0.5,
+		}
+	}
+}
+
+impl WarmStart {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.random_fraction.is_nan() || !(0. ..=1.).contains(&self.random_fraction) {
+			return Err(format!("warm_start.random_fraction must be in [0, 1], got {}", self.random_fraction))
+		}
+		Ok(())
+	}
+
+	// Doable paths to start from, empty for a fully random generation
+	pub fn paths(&self, simulation: &Simulation, max_depth: usize, time_weight: f32, scorer: BroScore, values: &Values) -> Result<Vec<Path>, String> {
+		let mut paths = self.files
+			.iter()
+			.map(|file| { read_path(simulation, file) })
+			.collect::<Result<Vec<Path>, String>>()?;
+		if self.greedy {
+			let config = BeamConfig::new(1, max_depth, time_weight, scorer, values.clone());
+			paths.push(flatten(&beam_solve(simulation.clone(), config, &Control::default())?));
+		}
+		Ok(paths
+			.into_iter()
+			.map(|path| {
+				let mut path = repair(simulation, path);
+				path.truncate(max_depth);
+				path
+			})
+			.collect())
+	}
+
+	// Paths of the generation made from the warm start paths
+	pub fn seeded_count(&self, generation_size: usize) -> usize {
+		generation_size - (generation_size as f32 * self.random_fraction).round() as usize
+	}
+}
+
+// A production as JSON, a production as printed by krpsim, or a trace of `cycle:process` lines
+pub fn read_path(simulation: &Simulation, file: &str) -> Result<Path, String> {
+	let content = read_to_string(file).map_err(|err| { format!("{}: {}", file, err) })?;
+	let path = parse_path(&content).ok_or(format!("{}: not a production nor a trace", file))?;
+	match path.iter().find(|step| { !simulation.processes.contains_key(*step) }) {
+		Some (step) => Err(format!("{}: unknown process {}", file, step)),
+		None => Ok(path),
+	}
+}
+
+fn parse_path(content: &str) -> Option<Path> {
+	let content = content.trim();
+	if let Ok (production) = serde_json::from_str::<Production>(content) {
+		return Some(flatten(&production))
+	}
+	if let Ok (path) = serde_json::from_str::<Path>(content) {
+		return Some(path)
+	}
+	// Process names can't hold parentheses, the printed tuples become JSON arrays
+	if let Some (line) = content.lines().find(|line| { line.starts_with('[') }) {
+		let json = line.replace('(', "[").replace(')', "]");
+		if let Ok (production) = serde_json::from_str::<Production>(&json) {
+			return Some(flatten(&production))
+		}
+	}
+	parse_trace(content)
+}
+
+// Steps sorted by cycle, lines that aren't `cycle:process` are ignored
+fn parse_trace(content: &str) -> Option<Path> {
+	let mut steps: Vec<(usize, String)> = content
+		.lines()
+		.filter_map(|line| {
+			let (cycle, process) = line.trim().split_once(':')?;
+			let process = process.trim();
+			if process.is_empty() || process.contains(char::is_whitespace) {
+				return None
+			}
+			Some((cycle.trim().parse().ok()?, process.to_string()))
+		})
+		.collect();
+	if steps.is_empty() {
+		return None
+	}
+	steps.sort_by_key(|(cycle, _)| { *cycle });
+	Some(steps.into_iter().map(|(_, process)| { process }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::parse_path;
+
+	#[test]
+	fn test_parse_path() {
+		let expected: Vec<String> = vec!["do_montant".to_string(), "do_fond".to_string(), "do_armoire_ikea".to_string()];
+		let json = "[[20, [\"do_montant\", \"do_fond\"]], [30, [\"do_armoire_ikea\"]]]";
+		let printed = "[(20, [\"do_montant\", \"do_fond\"]), (30, [\"do_armoire_ikea\"])]\nseed: 4\n";
+		let trace = "# ikea\n20:do_armoire_ikea\n0:do_montant\n0:do_fond\nno more process doable at time 50\n";
+		assert_eq!(parse_path(json), Some(expected.clone()));
+		assert_eq!(parse_path(printed), Some(expected.clone()));
+		assert_eq!(parse_path(trace), Some(expected));
+		assert_eq!(parse_path("nothing here"), None);
+	}
+}
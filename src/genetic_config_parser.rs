@@ -1,9 +1,174 @@
 extern crate serde_json;
 
-use crate::genetic::Config;
+use std::fs::read_to_string;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::{
+	genetic::Config,
+	mcts::Config as MctsConfig,
+	local_search::{annealing::Config as AnnealingConfig, tabu::Config as TabuConfig},
+	planner::Config as PlannerConfig,
+	beam::Config as BeamConfig,
+	nsga2::Config as Nsga2Config,
+	portfolio::Config as PortfolioConfig,
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "generic_config.json";
 
 pub fn parse_genetic_config<'a>(file_content: String) -> Result<Config, String> {
     // Parse the string of data into a GeneticConfig object
-    serde_json::from_str(&file_content)
-		.map_err(|err| format!("{:?}", err))
+    let config: Config = serde_json::from_str(&file_content)
+		.map_err(|err| format!("{:?}", err))?;
+	config.validate()?;
+	config.island_configs()?;
+	Ok(config)
+}
+
+// Parse the `section` object of the config file, defaults if it's missing
+pub fn parse_config_section<T: DeserializeOwned + Default>(file_content: &str, section: &str) -> Result<T, String> {
+    let content: serde_json::Value = serde_json::from_str(file_content)
+		.map_err(|err| format!("{:?}", err))?;
+    match content.get(section) {
+        Some (value) => serde_json::from_value(value.clone()).map_err(|err| format!("{}: {:?}", section, err)),
+        None => Ok(T::default()),
+    }
+}
+
+// Content of the config file with the `key=value` overrides applied.
+// Without a path, the default file is optional and every value defaults
+pub fn read_config(path: Option<&str>, overrides: &[String]) -> Result<String, String> {
+	let file_content = match path {
+		Some (path) => read_to_string(path).map_err(|err| format!("{}: {}", path, err))?,
+		None => read_to_string(DEFAULT_CONFIG_PATH).unwrap_or("{}".to_string()),
+	};
+	apply_overrides(&file_content, overrides)
+}
+
+// Config saved in a genetic checkpoint, set to resume from it, with the overrides applied
+pub fn read_checkpoint_config(path: &str, overrides: &[String]) -> Result<String, String> {
+	let checkpoint = read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+	let mut content: Value = serde_json::from_str(&checkpoint).map_err(|err| format!("{}: {}", path, err))?;
+	let mut config = content
+		.get_mut("config")
+		.filter(|config| { config.is_object() })
+		.ok_or(format!("{}: not a genetic checkpoint", path))?
+		.take();
+	config["resume"] = Value::String(path.to_string());
+	apply_overrides(&config.to_string(), overrides)
+}
+
+// Every key a config can have: the genetic keys, and a section per other solver
+fn defaults() -> Result<Value, String> {
+	fn value<T: Serialize + Default>() -> Result<Value, String> {
+		serde_json::to_value(T::default()).map_err(|err| { err.to_string() })
+	}
+	let mut defaults = value::<Config>()?;
+	let sections = vec![
+		("mcts", value::<MctsConfig>()?),
+		("annealing", value::<AnnealingConfig>()?),
+		("tabu", value::<TabuConfig>()?),
+		("planner", value::<PlannerConfig>()?),
+		("beam", value::<BeamConfig>()?),
+		("nsga2", value::<Nsga2Config>()?),
+		("portfolio", value::<PortfolioConfig>()?),
+	];
+	for (name, section) in sections {
+		defaults[name] = section;
+	}
+	Ok(defaults)
+}
+
+// A key is known when the config or the defaults have it. Below an empty default map or a
+// default that isn't an object, such as `values` or an unset option, any key goes
+fn is_known(defaults: &Value, content: &Value, key: &str) -> bool {
+	if content.pointer(&format!("/{}", key.replace('.', "/"))).is_some() {
+		return true
+	}
+	let mut value = defaults;
+	for name in key.split('.') {
+		match value {
+			Value::Object (map) if map.is_empty() => return true,
+			Value::Object (map) => match map.get(name) {
+				Some (next) => value = next,
+				None => return false,
+			},
+			_ => return true,
+		}
+	}
+	true
+}
+
+// Keys are dotted paths such as `mcts.iterations`, values are JSON or plain strings
+pub fn apply_overrides(file_content: &str, overrides: &[String]) -> Result<String, String> {
+	let mut content: Value = serde_json::from_str(file_content)
+		.map_err(|err| format!("{:?}", err))?;
+	let defaults = defaults()?;
+	for assignment in overrides {
+		let (key, value) = match assignment.find('=') {
+			Some (i) => (&assignment[..i], &assignment[i + 1..]),
+			None => return Err(format!("Override must be key=value, got {}", assignment)),
+		};
+		if !is_known(&defaults, &content, key) {
+			return Err(format!("Unknown config key: {}", key))
+		}
+		let value = serde_json::from_str(value).unwrap_or(Value::String(value.to_string()));
+		let mut target = &mut content;
+		for name in key.split('.') {
+			if name.is_empty() {
+				return Err(format!("Invalid override key: {}", key))
+			}
+			if !target.is_object() {
+				*target = Value::Object(Map::new());
+			}
+			target = target
+				.as_object_mut()
+				.ok_or(format!("Invalid override key: {}", key))?
+				.entry(name)
+				.or_insert(Value::Null);
+		}
+		*target = value;
+	}
+	Ok(content.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{apply_overrides, parse_config_section, parse_genetic_config};
+
+	#[test]
+	fn test_overrides() {
+		let overrides = vec![
+			"mcts.iterations=5".to_string(),
+			"crossover=order".to_string(),
+			"seed=3".to_string(),
+		];
+		let content = apply_overrides("{ \"mcts\": { \"max_depth\": 10 }, \"seed\": null }", &overrides).unwrap();
+		let mcts: serde_json::Value = parse_config_section(&content, "mcts").unwrap();
+
+		assert_eq!(mcts, serde_json::json!({ "iterations": 5, "max_depth": 10 }));
+		assert_eq!(parse_config_section::<String>(&content, "crossover").unwrap(), "order");
+		assert_eq!(parse_config_section::<Option<u64>>(&content, "seed").unwrap(), Some(3));
+		assert!(apply_overrides("{}", &["seed".to_string()]).is_err());
+		assert_eq!(apply_overrides("{}", &["mutaton_chance=0.5".to_string()]).err(), Some("Unknown config key: mutaton_chance".to_string()));
+		assert!(apply_overrides("{}", &["tabu.tenur=5".to_string()]).is_err());
+		assert!(apply_overrides("{}", &["tabu.tenure=5".to_string(), "values.euro={\"function\": \"logarithmic\"}".to_string()]).is_ok());
+	}
+
+	#[test]
+	fn test_genetic_validation() {
+		assert!(parse_genetic_config("{}".to_string()).is_ok());
+		assert_eq!(
+			parse_genetic_config("{ \"mutation_chance\": 1.5 }".to_string()).err(),
+			Some("mutation_chance must be in (0, 1], got 1.5".to_string())
+		);
+		assert!(parse_genetic_config("{ \"generation_size\": 0 }".to_string()).is_err());
+		assert!(parse_genetic_config("{ \"selection\": { \"strategy\": \"tournament\", \"size\": 0 } }".to_string()).is_err());
+		assert_eq!(
+			parse_genetic_config("{ \"islands\": { \"count\": 2, \"configs\": [{}, { \"crossover_rate\": 2 }] } }".to_string()).err(),
+			Some("islands.configs[1]: crossover_rate must be in [0, 1], got 2".to_string())
+		);
+	}
 }
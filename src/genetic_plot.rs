@@ -1,15 +1,106 @@
-use gnuplot::{Figure, Caption, Color};
+use std::fs::write;
+use std::path::Path as FilePath;
+
+use gnuplot::{AxesCommon, Figure, Caption, Color};
 
 use super::genetic::Stats;
 
-pub fn plot(stats: Stats) {
-	let x: Vec<usize> = (0..(stats.average_scores.len())).collect();
-	let y = stats.best_scores;
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 768;
+
+fn extension(path: &str) -> String {
+	FilePath::new(path)
+		.extension()
+		.map(|extension| { extension.to_string_lossy().to_lowercase() })
+		.unwrap_or_default()
+}
+
+pub fn check_extension(path: &str, extensions: &[&str]) -> Result<(), String> {
+	if extensions.contains(&extension(path).as_str()) {
+		Ok(())
+	} else {
+		Err(format!("{} must be a {} file", path, extensions.join(" or ")))
+	}
+}
+
+// Best, average and worst score of each generation, as svg or png depending on the extension
+pub fn plot(stats: &Stats, path: &str) -> Result<(), String> {
+	let x: Vec<usize> = (0..stats.best_scores.len()).collect();
 	let mut fg = Figure::new();
 	fg.axes2d()
-	.lines(&x, &y, &[Caption("A line"), Color("black")]);
-	match fg.show() {
-		Ok (_) => { println!("Plot success") },
-		Err (err) => {println!("{:?}", err)}
+		.set_title("Genetic scores", &[])
+		.set_x_label("Generation", &[])
+		.set_y_label("Score", &[])
+		.lines(&x, &stats.best_scores, &[Caption("Best"), Color("dark-green")])
+		.lines(&x, &stats.average_scores, &[Caption("Average"), Color("blue")])
+		.lines(&x, &stats.worst_scores, &[Caption("Worst"), Color("red")]);
+	check_extension(path, &["svg", "png"])?;
+	let saved = if extension(path) == "svg" {
+		fg.save_to_svg(path, WIDTH, HEIGHT)
+	} else {
+		fg.save_to_png(path, WIDTH, HEIGHT)
+	};
+	saved.map_err(|err| { format!("{}: {}", path, err) })
+}
+
+// One line per generation, the best path's steps separated by spaces
+pub fn to_csv(stats: &Stats) -> String {
+	let mut csv = "generation,best,average,worst,score_deviation,distinct_paths,edit_distance,infeasible_fraction,evaluations_per_second,best_path\n".to_string();
+	for generation in 0..stats.best_scores.len() {
+		csv += &format!("{},{},{},{},{:.3},{},{:.3},{:.3},{:.3},{}\n",
+			generation,
+			stats.best_scores[generation],
+			stats.average_scores[generation],
+			stats.worst_scores[generation],
+			stats.score_deviations[generation],
+			stats.distinct_paths[generation],
+			stats.edit_distances[generation],
+			stats.infeasible_fractions[generation],
+			stats.evaluations_per_second[generation],
+			stats.best_paths[generation].join(" "));
+	}
+	csv
+}
+
+// As csv or json depending on the extension, json also holds the stats of each island
+pub fn export(stats: &Stats, path: &str) -> Result<(), String> {
+	check_extension(path, &["csv", "json"])?;
+	let content = if extension(path) == "csv" {
+		to_csv(stats)
+	} else {
+		serde_json::to_string_pretty(stats).map_err(|err| { err.to_string() })?
+	};
+	write(path, content).map_err(|err| { format!("{}: {}", path, err) })
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::genetic::{Generation, Stats};
+	use super::{check_extension, to_csv};
+
+	#[test]
+	fn test_to_csv() {
+		let mut stats = Stats::new();
+		let (best, other) = (vec!["a".to_string(), "b".to_string()], vec!["b".to_string()]);
+		stats.push(Generation::new(&[10, 4, 1], &[&best[..], &other[..], &other[..]], best.clone(), 1, Duration::from_secs(2), 0, 0.1));
+		stats.push(Generation::new(&[12, 12], &[&other[..], &other[..]], other.clone(), 0, Duration::from_secs(1), 0, 0.1));
+		assert!(stats.validate().is_ok());
+		let csv = to_csv(&stats);
+		let mut lines = csv.lines().skip(1);
+		let first: Vec<&str> = lines.next().unwrap().split(',').collect();
+		assert_eq!(&first[..4], &["0", "10", "5", "1"]);
+		assert_eq!(first[4], "3.742");
+		assert_eq!(first[5], "2");
+		assert_eq!(first[7], "0.333");
+		assert_eq!(first[8], "1.500");
+		assert_eq!(first[9], "a b");
+		assert_eq!(lines.next().unwrap(), "1,12,12,12,0.000,1,0.000,0.000,2.000,b");
+		stats.islands.push(stats.clone());
+		stats.islands[0].mutation_chances.pop();
+		assert!(stats.validate().is_err());
+		assert!(check_extension("out.PNG", &["svg", "png"]).is_ok());
+		assert!(check_extension("out", &["csv", "json"]).is_err());
 	}
 }
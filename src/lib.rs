@@ -2,13 +2,26 @@ pub mod parser;
 pub mod ast;
 pub mod inventory;
 pub mod genetic;
+pub mod mcts;
+pub mod local_search;
+pub mod planner;
+pub mod beam;
+pub mod nsga2;
+pub mod portfolio;
 pub mod solver;
+pub mod control;
 pub mod check;
 pub mod genetic_config_parser;
 pub mod genetic_plot;
 pub mod utils;
 pub mod score;
 pub mod simulate;
+pub mod simplex;
+pub mod throughput;
+pub mod tune;
+
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(test)]
 mod check_tests;
@@ -16,5 +29,8 @@ mod check_tests;
 #[cfg(test)]
 mod solver_test;
 
+#[cfg(test)]
+mod local_search_test;
+
 #[cfg(test)]
 #[macro_use] extern crate maplit;
@@ -0,0 +1,97 @@
+use rand::{Rng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	score::{BroScore, Values},
+	solver::{Production, batchify},
+};
+use super::Neighbourhood;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Cooling {
+	Geometric,
+	Linear,
+	Logarithmic,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	iterations: usize,
+	max_depth: usize,
+	initial_temperature: f64,
+	cooling: Cooling,
+	cooling_rate: f64,
+	time_weight: f32,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			iterations: 10000,
+			max_depth: 100,
+			initial_temperature: 100.,
+			cooling: Cooling::Geometric,
+			cooling_rate: 0.999,
+			time_weight: 0.1,
+			scorer: BroScore::default(),
+			values: Values::new(),
+		}
+	}
+}
+
+impl Config {
+	fn temperature(&self, iteration: usize) -> f64 {
+		let k = iteration as f64;
+		match self.cooling {
+			Cooling::Geometric => self.initial_temperature * self.cooling_rate.powf(k),
+			Cooling::Linear => self.initial_temperature * (1. - k / self.iterations as f64),
+			Cooling::Logarithmic => self.initial_temperature / (1. + self.cooling_rate * (1. + k).ln()),
+		}
+	}
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
+	let neighbourhood = Neighbourhood::new(simulation, config.max_depth, config.time_weight, config.scorer, config.values.clone());
+	let mut current = neighbourhood.initial_path(rng);
+	let mut current_score = neighbourhood.score(&current)?;
+	let mut best = (current_score, current.clone());
+
+	for i in 0..config.iterations {
+		if control.is_cancelled() {
+			break
+		}
+		if let Some ((score, path)) = neighbourhood.best_known(control)? {
+			if score > best.0 {
+				current = path;
+				current_score = score;
+				best = (current_score, current.clone());
+			}
+		}
+		let (_, candidate) = match neighbourhood.neighbour(&current, rng) {
+			Some (neighbour) => neighbour,
+			None => break,
+		};
+		let candidate_score = neighbourhood.score(&candidate)?;
+		let delta = (candidate_score - current_score) as f64;
+		let temperature = config.temperature(i);
+		let accepted = delta >= 0.
+			|| (temperature > 0. && rng.gen_range(0., 1.) < (delta / temperature).exp());
+		if accepted {
+			current = candidate;
+			current_score = candidate_score;
+			if current_score > best.0 {
+				control.incumbent(&current);
+				best = (current_score, current.clone());
+			}
+		}
+	}
+	batchify(neighbourhood.simulation(), best.1)
+}
@@ -0,0 +1,133 @@
+pub mod annealing;
+pub mod tabu;
+
+use rand::Rng;
+
+use crate::{
+	ast::Simulation,
+	check::manage_resources,
+	control::Control,
+	score::{Score, Scorer, BroScore, Values},
+	solver::{Path, Step},
+	simulate::{simulate, generate_path},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Move {
+	Swap(usize, usize),
+	Insert(usize, Step),
+	Delete(usize),
+	Replace(usize, Step),
+}
+
+impl Move {
+	pub fn apply(&self, path: &Path) -> Path {
+		let mut path = path.clone();
+		match self {
+			Move::Swap(i, j) => path.swap(*i, *j),
+			Move::Insert(i, step) => path.insert(*i, step.clone()),
+			Move::Delete(i) => { path.remove(*i); },
+			Move::Replace(i, step) => path[*i] = step.clone(),
+		};
+		path
+	}
+}
+
+// Drop the steps that can't be done anymore, keeping the rest of the path in order
+pub fn repair(simulation: &Simulation, path: Path) -> Path {
+	if simulate(simulation, &path, false).is_ok() {
+		return path
+	}
+	let mut inventory = simulation.inventory.clone();
+	path
+		.into_iter()
+		.filter(|step| {
+			let process = match simulation.processes.get(step) {
+				Some (process) => process,
+				None => return false,
+			};
+			match manage_resources(inventory.clone(), process) {
+				Ok (updated_inventory) => {
+					inventory = updated_inventory;
+					true
+				},
+				Err (_) => false,
+			}
+		})
+		.collect()
+}
+
+// Move and repair machinery shared by the local search solvers
+pub struct Neighbourhood {
+	simulation: Simulation,
+	scorer: Scorer,
+	step_names: Vec<Step>,
+	max_depth: usize,
+}
+
+impl Neighbourhood {
+	pub fn new(simulation: Simulation, max_depth: usize, time_weight: f32, scorer: BroScore, values: Values) -> Self {
+		let mut step_names: Vec<Step> = simulation.processes.keys().cloned().collect();
+		step_names.sort();
+		Self {
+			simulation: simulation.clone(),
+			scorer: Scorer::new(simulation, time_weight, scorer).with_values(values).with_horizon(max_depth),
+			step_names,
+			max_depth,
+		}
+	}
+
+	pub fn simulation(&self) -> &Simulation {
+		&self.simulation
+	}
+
+	pub fn initial_path<R: Rng>(&self, rng: &mut R) -> Path {
+		generate_path(&self.simulation, self.max_depth, &self.simulation.inventory, rng)
+	}
+
+	pub fn score(&self, path: &Path) -> Result<Score, String> {
+		self.scorer.score(path)
+	}
+
+	// Best path of the solvers running beside this one, made to fit this search, with its score
+	pub fn best_known(&self, control: &Control) -> Result<Option<(Score, Path)>, String> {
+		match control.best_known() {
+			Some (path) => {
+				let mut path = repair(&self.simulation, path);
+				path.truncate(self.max_depth);
+				Ok(Some((self.score(&path)?, path)))
+			},
+			None => Ok(None),
+		}
+	}
+
+	pub fn random_move<R: Rng>(&self, path: &Path, rng: &mut R) -> Option<Move> {
+		let len = path.len();
+		if self.step_names.is_empty() {
+			return None
+		}
+		let step = self.step_names[rng.gen_range(0, self.step_names.len())].clone();
+		if len == 0 {
+			return if self.max_depth > 0 { Some(Move::Insert(0, step)) } else { None }
+		}
+		match rng.gen_range(0, 4) {
+			0 if len > 1 => {
+				let i = rng.gen_range(0, len);
+				let j = (i + rng.gen_range(1, len)) % len;
+				Some(Move::Swap(i, j))
+			},
+			1 if len < self.max_depth => Some(Move::Insert(rng.gen_range(0, len + 1), step)),
+			2 => Some(Move::Delete(rng.gen_range(0, len))),
+			_ => Some(Move::Replace(rng.gen_range(0, len), step)),
+		}
+	}
+
+	// Random move applied to the path, repaired to stay doable
+	pub fn neighbour<R: Rng>(&self, path: &Path, rng: &mut R) -> Option<(Move, Path)> {
+		self.random_move(path, rng)
+			.map(|movement| {
+				let neighbour = repair(&self.simulation, movement.apply(path));
+				(movement, neighbour)
+			})
+	}
+}
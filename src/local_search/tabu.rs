@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	score::{Score, BroScore, Values},
+	solver::{Production, Path, batchify},
+};
+use super::Neighbourhood;
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	iterations: usize,
+	max_depth: usize,
+	neighbourhood_size: usize,
+	tenure: usize,
+	time_weight: f32,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			iterations: 1000,
+			max_depth: 100,
+			neighbourhood_size: 20,
+			tenure: 10,
+			time_weight: 0.1,
+			scorer: BroScore::default(),
+			values: Values::new(),
+		}
+	}
+}
+
+// Paths left by the last accepted moves. Moves are kept out by where they lead rather than by their
+// indices, which shift after each move and after the repair
+pub struct TabuList {
+	paths: VecDeque<Path>,
+	tenure: usize,
+}
+
+impl TabuList {
+	pub fn new(tenure: usize) -> Self {
+		Self { paths: VecDeque::with_capacity(tenure + 1), tenure }
+	}
+
+	pub fn push(&mut self, path: Path) {
+		self.paths.push_back(path);
+		if self.paths.len() > self.tenure {
+			self.paths.pop_front();
+		}
+	}
+
+	// Tabu moves are still allowed when they beat the best path found
+	pub fn allows(&self, candidate: &Path, score: Score, best: Score) -> bool {
+		score > best || !self.paths.contains(candidate)
+	}
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
+	let neighbourhood = Neighbourhood::new(simulation, config.max_depth, config.time_weight, config.scorer, config.values.clone());
+	let mut tabu_list = TabuList::new(config.tenure);
+	let mut current = neighbourhood.initial_path(rng);
+	let mut best = (neighbourhood.score(&current)?, current.clone());
+
+	for _ in 0..config.iterations {
+		if control.is_cancelled() {
+			break
+		}
+		if let Some ((score, path)) = neighbourhood.best_known(control)? {
+			if score > best.0 {
+				current = path;
+				best = (score, current.clone());
+			}
+		}
+		let mut chosen: Option<(Score, Path)> = None;
+		for _ in 0..config.neighbourhood_size {
+			let (_, candidate) = match neighbourhood.neighbour(&current, rng) {
+				Some (neighbour) => neighbour,
+				None => break,
+			};
+			let score = neighbourhood.score(&candidate)?;
+			if !tabu_list.allows(&candidate, score, best.0) {
+				continue
+			}
+			match &chosen {
+				Some ((chosen_score, _)) if *chosen_score >= score => {},
+				_ => { chosen = Some((score, candidate)) },
+			}
+		}
+		let (score, candidate) = match chosen {
+			Some (chosen) => chosen,
+			None => continue,
+		};
+		tabu_list.push(std::mem::replace(&mut current, candidate));
+		if score > best.0 {
+			control.incumbent(&current);
+			best = (score, current.clone());
+		}
+	}
+	batchify(neighbourhood.simulation(), best.1)
+}
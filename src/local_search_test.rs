@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::test_utils::test_provider;
+use crate::control::Control;
+use crate::local_search::{Move, annealing, repair, tabu::TabuList};
+use crate::score::{BroScore, Scorer};
+use crate::solver::{Path, flatten};
+
+fn to_path(steps: &[&str]) -> Path {
+	steps.iter().map(|step| { step.to_string() }).collect()
+}
+
+#[test]
+fn repair_drops_undoable_steps() {
+	let simulation = test_provider("ressources/simple".to_string()).unwrap();
+	let path = to_path(&["realisation_produit", "achat_materiel", "livraison", "realisation_produit", "livraison"]);
+	let expected = to_path(&["achat_materiel", "realisation_produit", "livraison"]);
+	assert_eq!(repair(&simulation, path), expected);
+}
+
+#[test]
+fn tabu_refuses_going_back_unless_it_beats_the_best() {
+	let simulation = test_provider("ressources/ikea".to_string()).unwrap();
+	let left = to_path(&["do_montant", "do_etagere"]);
+	let current = repair(&simulation, Move::Insert(0, "do_fond".to_string()).apply(&left));
+	let mut tabu_list = TabuList::new(10);
+	tabu_list.push(left.clone());
+	let back = repair(&simulation, Move::Delete(0).apply(&current));
+	assert_eq!(back, left);
+	assert!(!tabu_list.allows(&back, 10, 10));
+	assert!(tabu_list.allows(&back, 11, 10));
+	// The same path, reached through a move the repair shifts
+	let repaired = repair(&simulation, Move::Replace(0, "do_armoire_ikea".to_string()).apply(&current));
+	assert_eq!(repaired, left);
+	assert!(!tabu_list.allows(&repaired, 10, 10));
+	assert!(tabu_list.allows(&current, 10, 10));
+}
+
+#[test]
+fn annealing_carries_on_from_the_best_known_path() {
+	let simulation = test_provider("ressources/ikea".to_string()).unwrap();
+	let known = to_path(&["do_montant", "do_montant", "do_fond", "do_etagere", "do_etagere", "do_etagere", "do_armoire_ikea"]);
+	let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo);
+	let config: annealing::Config = serde_json::from_str("{ \"iterations\": 1 }").unwrap();
+	let best_known = known.clone();
+	let control = Control::default().with_best_known(Arc::new(move || { Some(best_known.clone()) }));
+	let production = annealing::solve(simulation, config, &control, &mut StdRng::seed_from_u64(0)).unwrap();
+	assert!(scorer.score(&flatten(&production)).unwrap() >= scorer.score(&known).unwrap());
+}
@@ -0,0 +1,255 @@
+extern crate serde;
+extern crate rand;
+
+use std::time::{Duration as WallDuration, Instant};
+
+use rand::{Rng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::{Simulation},
+	control::Control,
+	inventory::Inventory,
+	score::{Score, Scorer, BroScore, Values},
+	solver::{Production, Path, batchify},
+	simulate::{get_available_steps, generate_path},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	iterations: usize,
+	// milliseconds, 0 for no limit
+	time_limit: u64,
+	max_depth: usize,
+	exploration: f64,
+	time_weight: f32,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			iterations: 1000,
+			time_limit: 0,
+			max_depth: 100,
+			exploration: 2f64.sqrt(),
+			time_weight: 0.1,
+			scorer: BroScore::default(),
+			values: Values::new(),
+		}
+	}
+}
+
+struct Node {
+	step: Option<String>,
+	parent: Option<usize>,
+	children: Vec<usize>,
+	untried: Vec<(String, Inventory)>,
+	inventory: Inventory,
+	depth: usize,
+	visits: usize,
+	reward: f64,
+}
+
+impl Node {
+	fn new(step: Option<String>, parent: Option<usize>, inventory: Inventory, depth: usize, untried: Vec<(String, Inventory)>) -> Self {
+		Self {
+			step,
+			parent,
+			children: vec![],
+			untried,
+			inventory,
+			depth,
+			visits: 0,
+			reward: 0.,
+		}
+	}
+}
+
+struct MctsSolver {
+	iterations: usize,
+	time_limit: Option<WallDuration>,
+	max_depth: usize,
+	exploration: f64,
+	simulation: Simulation,
+	scorer: Scorer,
+	nodes: Vec<Node>,
+	best: Option<(Score, Path)>,
+	score_bounds: (Score, Score),
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
+	let mut solver = MctsSolver::new(config, simulation);
+	solver.solve(control, rng)
+}
+
+impl MctsSolver {
+	pub fn new(config: Config, simulation: Simulation) -> Self {
+		let root_untried = get_available_steps(&simulation, &simulation.inventory);
+		let root = Node::new(None, None, simulation.inventory.clone(), 0, root_untried);
+		Self {
+			iterations: config.iterations,
+			time_limit: if config.time_limit > 0 { Some(WallDuration::from_millis(config.time_limit)) } else { None },
+			max_depth: config.max_depth,
+			exploration: config.exploration,
+			simulation: simulation.clone(),
+			scorer: Scorer::new(simulation, config.time_weight, config.scorer).with_values(config.values.clone()).with_horizon(config.max_depth),
+			nodes: vec![root],
+			best: None,
+			score_bounds: (Score::MAX, Score::MIN),
+		}
+	}
+
+	pub fn solve(&mut self, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
+		let start = Instant::now();
+		for _ in 0..self.iterations {
+			if control.is_cancelled() {
+				break
+			}
+			if let Some(time_limit) = self.time_limit {
+				if start.elapsed() >= time_limit {
+					break
+				}
+			}
+			let leaf = self.select();
+			let node = self.expand(leaf, rng);
+			let score = self.rollout(node, control, rng)?;
+			self.backpropagate(node, score);
+		}
+		let best_path = self.best.take().map(|(_, path)| { path }).unwrap_or(vec![]);
+		batchify(&self.simulation, best_path)
+	}
+
+	// Average reward of a node, normalized between 0 and 1 with the scores seen so far
+	fn exploitation(&self, node: &Node) -> f64 {
+		let (min, max) = self.score_bounds;
+		if max <= min {
+			return 0.
+		}
+		let average = node.reward / node.visits as f64;
+		(average - min as f64) / (max - min) as f64
+	}
+
+	fn uct(&self, parent: &Node, child: &Node) -> f64 {
+		let exploration = self.exploration * ((parent.visits as f64).ln() / child.visits as f64).sqrt();
+		self.exploitation(child) + exploration
+	}
+
+	// Walk down the fully expanded nodes following the best UCT
+	fn select(&self) -> usize {
+		let mut current = 0;
+		loop {
+			let node = &self.nodes[current];
+			if !node.untried.is_empty() || node.children.is_empty() {
+				return current
+			}
+			current = *node.children
+				.iter()
+				.max_by(|a, b| {
+					let uct_a = self.uct(node, &self.nodes[**a]);
+					let uct_b = self.uct(node, &self.nodes[**b]);
+					uct_a.partial_cmp(&uct_b).unwrap_or(std::cmp::Ordering::Equal)
+				})
+				.unwrap_or(&current);
+		}
+	}
+
+	// Add one untried step as a new child, terminal nodes are returned as is
+	fn expand(&mut self, index: usize, rng: &mut StdRng) -> usize {
+		let node = &mut self.nodes[index];
+		if node.untried.is_empty() {
+			return index
+		}
+		let i = rng.gen_range(0, node.untried.len());
+		let (step, inventory) = node.untried.swap_remove(i);
+		let depth = node.depth + 1;
+		let untried = if depth < self.max_depth {
+			get_available_steps(&self.simulation, &inventory)
+		} else {
+			vec![]
+		};
+		let child = Node::new(Some(step), Some(index), inventory, depth, untried);
+		let child_index = self.nodes.len();
+		self.nodes.push(child);
+		self.nodes[index].children.push(child_index);
+		child_index
+	}
+
+	fn path_to(&self, index: usize) -> Path {
+		let mut path: Path = vec![];
+		let mut current = Some(index);
+		while let Some(i) = current {
+			let node = &self.nodes[i];
+			if let Some(step) = &node.step {
+				path.push(step.clone());
+			}
+			current = node.parent;
+		}
+		path.reverse();
+		path
+	}
+
+	// Complete the node's path with random doable steps and score the result
+	fn rollout(&mut self, index: usize, control: &Control, rng: &mut StdRng) -> Result<Score, String> {
+		let node = &self.nodes[index];
+		let rest = generate_path(&self.simulation, self.max_depth - node.depth, &node.inventory, rng);
+		let path = [&self.path_to(index)[..], &rest[..]].concat();
+		let score = self.scorer.score(&path)?;
+		let (min, max) = self.score_bounds;
+		self.score_bounds = (min.min(score), max.max(score));
+		match &self.best {
+			Some ((best_score, _)) if *best_score >= score => {},
+			_ => {
+				control.incumbent(&path);
+				self.best = Some((score, path));
+			},
+		}
+		Ok(score)
+	}
+
+	fn backpropagate(&mut self, index: usize, score: Score) {
+		let mut current = Some(index);
+		while let Some(i) = current {
+			let node = &mut self.nodes[i];
+			node.visits += 1;
+			node.reward += score as f64;
+			current = node.parent;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::ast::parse;
+	use crate::check::{check, Output};
+	use crate::control::Control;
+	use crate::score::Scorer;
+	use crate::solver::flatten;
+	use super::{solve, Config};
+
+	#[test]
+	fn test_best_rollout_is_doable() {
+		for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			let scorer = Scorer::new(simulation.clone(), 0.1, Default::default());
+			let run = |iterations: usize| {
+				let config = Config { iterations, max_depth: 30, ..Config::default() };
+				let production = solve(simulation.clone(), config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+				flatten(&production)
+			};
+			// A single iteration is a rollout from the root, later rollouts only replace it when better
+			let root_rollout = run(1);
+			let best = run(200);
+			assert!(check(simulation.clone(), Output { steps: best.clone() }).is_ok(), "{}", name);
+			assert!(scorer.score(&best).unwrap() >= scorer.score(&root_rollout).unwrap(), "{}", name);
+		}
+	}
+}
@@ -0,0 +1,523 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs::{read_to_string, write};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	genetic::Crossover,
+	local_search::repair,
+	solver::{Production, Path, Duration, batchify, flatten},
+	simulate::{simulate, generate_path},
+	utils::{parallel_map, worker_count},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Pick {
+	// Position in the front
+	Index(usize),
+	// One per objective, in the front's order. Objectives are scaled to [0, 1] over the
+	// front first, empty weighs them all the same
+	Weights(Vec<f64>),
+}
+
+impl Default for Pick {
+	fn default() -> Self {
+		Pick::Weights(vec![])
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	generation_size: usize,
+	iterations: usize,
+	max_depth: usize,
+	mutation_chance: f32,
+	crossover: Crossover,
+	crossover_rate: f32,
+	// Threads scoring the paths, 0 for one per core
+	workers: usize,
+	// Schedule of the front returned as the production
+	pick: Pick,
+	// File the front is written to, to pick another schedule later
+	front: Option<String>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			generation_size: 100,
+			iterations: 50,
+			max_depth: 100,
+			mutation_chance: 0.1,
+			crossover: Crossover::default(),
+			crossover_rate: 0.7,
+			workers: 0,
+			pick: Pick::default(),
+			front: None,
+		}
+	}
+}
+
+impl Config {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.generation_size < 2 {
+			return Err(format!("nsga2.generation_size must be at least 2, got {}", self.generation_size))
+		}
+		if self.max_depth == 0 {
+			return Err("nsga2.max_depth must be greater than 0".to_string())
+		}
+		if self.mutation_chance.is_nan() || !(0. ..=1.).contains(&self.mutation_chance) {
+			return Err(format!("nsga2.mutation_chance must be in [0, 1], got {}", self.mutation_chance))
+		}
+		if self.crossover_rate.is_nan() || !(0. ..=1.).contains(&self.crossover_rate) {
+			return Err(format!("nsga2.crossover_rate must be in [0, 1], got {}", self.crossover_rate))
+		}
+		Ok(())
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Objectives {
+	// Final quantity of each optimized resource, higher is better
+	pub resources: Vec<usize>,
+	// Lower is better
+	pub makespan: Duration,
+}
+
+impl Objectives {
+	fn new(simulation: &Simulation, path: &Path) -> Result<Self, String> {
+		let (inventory, makespan) = simulate(simulation, path, true)?;
+		let resources = simulation.optimize
+			.iter()
+			.map(|name| { inventory.get(name).copied().unwrap_or(0) })
+			.collect();
+		Ok(Self { resources, makespan })
+	}
+
+	// No worse on every objective and better on one
+	pub fn dominates(&self, other: &Objectives) -> bool {
+		let no_worse = self.makespan <= other.makespan
+			&& self.resources.iter().zip(&other.resources).all(|(a, b)| { a >= b });
+		no_worse && self != other
+	}
+
+	// Higher is better for all of them, makespan last
+	fn values(&self) -> Vec<f64> {
+		let mut values: Vec<f64> = self.resources.iter().map(|quantity| { *quantity as f64 }).collect();
+		values.push(-(self.makespan as f64));
+		values
+	}
+}
+
+// Front of each individual, 0 for the non-dominated ones
+fn non_dominated_ranks(objectives: &[Objectives]) -> Vec<usize> {
+	let count = objectives.len();
+	let mut dominators = vec![0; count];
+	let mut dominated: Vec<Vec<usize>> = vec![vec![]; count];
+	for i in 0..count {
+		for j in i + 1..count {
+			if objectives[i].dominates(&objectives[j]) {
+				dominated[i].push(j);
+				dominators[j] += 1;
+			} else if objectives[j].dominates(&objectives[i]) {
+				dominated[j].push(i);
+				dominators[i] += 1;
+			}
+		}
+	}
+	let mut ranks = vec![0; count];
+	let mut front: Vec<usize> = (0..count).filter(|i| { dominators[*i] == 0 }).collect();
+	let mut rank = 0;
+	while !front.is_empty() {
+		let mut next = vec![];
+		for i in front {
+			ranks[i] = rank;
+			for j in &dominated[i] {
+				dominators[*j] -= 1;
+				if dominators[*j] == 0 {
+					next.push(*j);
+				}
+			}
+		}
+		front = next;
+		rank += 1;
+	}
+	ranks
+}
+
+// Distance to the neighbours of each individual in its front, the ends of a front are infinitely far
+fn crowding_distances(objectives: &[Objectives], ranks: &[usize]) -> Vec<f64> {
+	let values: Vec<Vec<f64>> = objectives.iter().map(|objectives| { objectives.values() }).collect();
+	// Values of each objective over the individuals
+	let columns: Vec<Vec<f64>> = (0..values.first().map_or(0, |values| { values.len() }))
+		.map(|objective| { values.iter().map(|values| { values[objective] }).collect() })
+		.collect();
+	let mut distances = vec![0.; objectives.len()];
+	let fronts = ranks.iter().max().map_or(0, |rank| { rank + 1 });
+	for rank in 0..fronts {
+		let front: Vec<usize> = (0..objectives.len()).filter(|i| { ranks[*i] == rank }).collect();
+		for column in &columns {
+			let mut sorted = front.clone();
+			sorted.sort_by(|a, b| { column[*a].partial_cmp(&column[*b]).unwrap_or(Ordering::Equal) });
+			let (first, last) = match (sorted.first(), sorted.last()) {
+				(Some (first), Some (last)) => (*first, *last),
+				_ => continue,
+			};
+			let range = column[last] - column[first];
+			distances[first] = f64::INFINITY;
+			distances[last] = f64::INFINITY;
+			if range <= 0. {
+				continue
+			}
+			for window in sorted.windows(3) {
+				distances[window[1]] += (column[window[2]] - column[window[0]]) / range;
+			}
+		}
+	}
+	distances
+}
+
+// Lower front first, then the most isolated
+fn crowded_cmp(fitness: &[(usize, f64)], a: usize, b: usize) -> Ordering {
+	let (rank_a, distance_a) = fitness[a];
+	let (rank_b, distance_b) = fitness[b];
+	rank_a.cmp(&rank_b).then(distance_b.partial_cmp(&distance_a).unwrap_or(Ordering::Equal))
+}
+
+fn fitness(objectives: &[Objectives]) -> Vec<(usize, f64)> {
+	let ranks = non_dominated_ranks(objectives);
+	let distances = crowding_distances(objectives, &ranks);
+	ranks.into_iter().zip(distances).collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Solution {
+	pub objectives: Objectives,
+	pub production: Production,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Front {
+	// Optimized resources, then "time"
+	pub names: Vec<String>,
+	// Non-dominated schedules by makespan, no two with the same objectives
+	pub solutions: Vec<Solution>,
+}
+
+impl Front {
+	pub fn pick(&self, pick: &Pick) -> Result<&Solution, String> {
+		match pick {
+			Pick::Index (index) => self.solutions
+				.get(*index)
+				.ok_or(format!("The front has {} schedules, no schedule {}", self.solutions.len(), index)),
+			Pick::Weights (weights) => {
+				let weights = if weights.is_empty() { vec![1.; self.names.len()] } else { weights.clone() };
+				if weights.len() != self.names.len() {
+					return Err(format!("{} weights given for {} objectives ({})", weights.len(), self.names.len(), self.names.join(", ")))
+				}
+				if weights.iter().any(|weight| { !weight.is_finite() || *weight < 0. }) {
+					return Err("Weights must be positive numbers".to_string())
+				}
+				let values: Vec<Vec<f64>> = self.solutions.iter().map(|solution| { solution.objectives.values() }).collect();
+				let bounds: Vec<(f64, f64)> = (0..weights.len())
+					.map(|objective| {
+						values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), values| {
+							(min.min(values[objective]), max.max(values[objective]))
+						})
+					})
+					.collect();
+				let scores: Vec<f64> = values
+					.iter()
+					.map(|values| {
+						values.iter().zip(&bounds).zip(&weights).map(|((value, (min, max)), weight)| {
+							if max > min { weight * (value - min) / (max - min) } else { 0. }
+						})
+						.sum()
+					})
+					.collect();
+				// Ties go to the longest schedule, which produces the most
+				let best = scores
+					.iter()
+					.enumerate()
+					.fold(None, |best: Option<(usize, f64)>, (i, score)| {
+						match best {
+							Some ((_, best_score)) if best_score > *score => best,
+							_ => Some((i, *score)),
+						}
+					})
+					.ok_or("The front is empty")?;
+				Ok(&self.solutions[best.0])
+			},
+		}
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		let content = serde_json::to_string_pretty(self).map_err(|err| { err.to_string() })?;
+		write(path, content).map_err(|err| { format!("{}: {}", path, err) })
+	}
+
+	pub fn load(path: &str) -> Result<Self, String> {
+		let content = read_to_string(path).map_err(|err| { format!("{}: {}", path, err) })?;
+		serde_json::from_str(&content).map_err(|err| { format!("{}: {}", path, err) })
+	}
+}
+
+impl fmt::Display for Front {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:<4}", "#")?;
+		for name in &self.names {
+			write!(f, " {:>12}", name)?;
+		}
+		writeln!(f, " {:>8}", "steps")?;
+		for (i, solution) in self.solutions.iter().enumerate() {
+			write!(f, "{:<4}", i)?;
+			for quantity in &solution.objectives.resources {
+				write!(f, " {:>12}", quantity)?;
+			}
+			let steps: usize = solution.production.iter().map(|(_, path)| { path.len() }).sum();
+			writeln!(f, " {:>12} {:>8}", solution.objectives.makespan, steps)?;
+		}
+		Ok(())
+	}
+}
+
+struct Nsga2Solver {
+	generation_size: usize,
+	iterations: usize,
+	max_depth: usize,
+	mutation_chance: f32,
+	crossover: Crossover,
+	crossover_rate: f32,
+	workers: usize,
+	simulation: Simulation,
+}
+
+// Prints the front and returns the picked schedule
+pub fn solve(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
+	let (production, front) = solve_with_front(simulation, config, control, rng)?;
+	eprint!("{}", front);
+	Ok(production)
+}
+
+// Picked schedule and the whole front, which is saved if the config asks for it
+pub fn solve_with_front(simulation: Simulation, config: Config, control: &Control, rng: &mut StdRng) -> Result<(Production, Front), String> {
+	let front = run(simulation, &config, control, rng)?;
+	if let Some (path) = &config.front {
+		front.save(path)?;
+	}
+	let production = front.pick(&config.pick)?.production.clone();
+	Ok((production, front))
+}
+
+pub fn run(simulation: Simulation, config: &Config, control: &Control, rng: &mut StdRng) -> Result<Front, String> {
+	config.validate()?;
+	let solver = Nsga2Solver::new(config, simulation);
+	let mut population = solver.evaluate(solver.generate(rng));
+	let mut incumbent: Option<Path> = None;
+	for _ in 0..solver.iterations {
+		if control.is_cancelled() {
+			break
+		}
+		let children = solver.evaluate(solver.offspring(&population, rng)?);
+		population.extend(children);
+		population = solver.survivors(population);
+		// The schedule that would be picked now
+		if let Some (solution) = solver.front(&population).as_ref().and_then(|front| { front.pick(&config.pick).ok() }) {
+			let path = flatten(&solution.production);
+			if incumbent.as_ref() != Some(&path) {
+				control.incumbent(&path);
+				incumbent = Some(path);
+			}
+		}
+	}
+	solver.front(&population).ok_or_else(|| { "No schedule could be simulated".to_string() })
+}
+
+impl Nsga2Solver {
+	fn new(config: &Config, simulation: Simulation) -> Self {
+		Self {
+			generation_size: config.generation_size,
+			iterations: config.iterations,
+			max_depth: config.max_depth,
+			mutation_chance: config.mutation_chance,
+			crossover: config.crossover,
+			crossover_rate: config.crossover_rate,
+			workers: worker_count(config.workers),
+			simulation,
+		}
+	}
+
+	// Random doable paths of random lengths, to start with schedules of every makespan
+	fn generate(&self, rng: &mut StdRng) -> Vec<Path> {
+		let seeds: Vec<u64> = (0..self.generation_size).map(|_| { rng.gen() }).collect();
+		let simulation = &self.simulation;
+		let max_depth = self.max_depth;
+		parallel_map(&seeds, self.workers, |seed| {
+			let mut rng = StdRng::seed_from_u64(*seed);
+			let len = rng.gen_range(1, max_depth + 1);
+			generate_path(simulation, len, &simulation.inventory, &mut rng)
+		})
+	}
+
+	// Paths that can't be simulated are dropped
+	fn evaluate(&self, paths: Vec<Path>) -> Vec<(Path, Objectives)> {
+		let simulation = &self.simulation;
+		let objectives = parallel_map(&paths, self.workers, |path| { Objectives::new(simulation, path).ok() });
+		paths
+			.into_iter()
+			.zip(objectives)
+			.filter_map(|(path, objectives)| { objectives.map(|objectives| { (path, objectives) }) })
+			.collect()
+	}
+
+	// Binary tournaments on the crowded comparison
+	fn offspring(&self, population: &[(Path, Objectives)], rng: &mut StdRng) -> Result<Vec<Path>, String> {
+		if population.is_empty() {
+			return Ok(self.generate(rng))
+		}
+		let objectives: Vec<Objectives> = population.iter().map(|(_, objectives)| { objectives.clone() }).collect();
+		let fitness = fitness(&objectives);
+		let tournament = |rng: &mut StdRng| {
+			let a = rng.gen_range(0, population.len());
+			let b = rng.gen_range(0, population.len());
+			let winner = if crowded_cmp(&fitness, b, a) == Ordering::Less { b } else { a };
+			&population[winner].0
+		};
+		(0..self.generation_size)
+			.map(|_| {
+				let first = tournament(rng);
+				let mut child = if rng.gen_range(0., 1.) < self.crossover_rate {
+					let second = tournament(rng);
+					let mut child = repair(&self.simulation, self.crossover.apply(first, second, rng));
+					child.truncate(self.max_depth);
+					child
+				} else {
+					first.clone()
+				};
+				if rng.gen_range(0., 1.) < self.mutation_chance {
+					child = self.mutate(child, rng)?;
+				}
+				Ok(child)
+			})
+			.collect()
+	}
+
+	// Keeps a random head and grows a random tail, so mutations can shorten or lengthen the schedule
+	fn mutate(&self, mut path: Path, rng: &mut StdRng) -> Result<Path, String> {
+		path.truncate(rng.gen_range(0, path.len() + 1));
+		let (inventory, _) = simulate(&self.simulation, &path, false)?;
+		let len = rng.gen_range(0, self.max_depth - path.len() + 1);
+		let tail = generate_path(&self.simulation, len, &inventory, rng);
+		path.extend(tail);
+		Ok(path)
+	}
+
+	// Best fronts first, the last one that fits partly by crowding distance
+	fn survivors(&self, population: Vec<(Path, Objectives)>) -> Vec<(Path, Objectives)> {
+		let objectives: Vec<Objectives> = population.iter().map(|(_, objectives)| { objectives.clone() }).collect();
+		let fitness = fitness(&objectives);
+		let mut order: Vec<usize> = (0..population.len()).collect();
+		order.sort_by(|a, b| { crowded_cmp(&fitness, *a, *b) });
+		order.truncate(self.generation_size);
+		order.sort_unstable();
+		population
+			.into_iter()
+			.enumerate()
+			.filter(|(i, _)| { order.binary_search(i).is_ok() })
+			.map(|(_, individual)| { individual })
+			.collect()
+	}
+
+	fn front(&self, population: &[(Path, Objectives)]) -> Option<Front> {
+		let objectives: Vec<Objectives> = population.iter().map(|(_, objectives)| { objectives.clone() }).collect();
+		let ranks = non_dominated_ranks(&objectives);
+		let mut members: Vec<&(Path, Objectives)> = population
+			.iter()
+			.zip(ranks)
+			.filter(|(_, rank)| { *rank == 0 })
+			.map(|(individual, _)| { individual })
+			.collect();
+		members.sort_by(|(_, a), (_, b)| { a.makespan.cmp(&b.makespan).then(b.resources.cmp(&a.resources)) });
+		members.dedup_by(|(_, a), (_, b)| { a == b });
+		let solutions = members
+			.into_iter()
+			.map(|(path, objectives)| {
+				Ok(Solution { objectives: objectives.clone(), production: batchify(&self.simulation, path.clone())? })
+			})
+			.collect::<Result<Vec<Solution>, String>>()
+			.ok()?;
+		if solutions.is_empty() {
+			return None
+		}
+		let mut names = self.simulation.optimize.clone();
+		names.push("time".to_string());
+		Some(Front { names, solutions })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::{ast::parse, control::Control};
+	use super::{Config, Front, Objectives, Pick, Solution, crowding_distances, non_dominated_ranks, run};
+
+	fn objectives(resources: Vec<usize>, makespan: usize) -> Objectives {
+		Objectives { resources, makespan }
+	}
+
+	#[test]
+	fn test_non_dominated_ranks() {
+		let population = vec![
+			objectives(vec![1], 10),
+			objectives(vec![2], 20),
+			objectives(vec![1], 20),
+			objectives(vec![0], 30),
+			objectives(vec![2], 20),
+		];
+		assert_eq!(non_dominated_ranks(&population), vec![0, 0, 1, 2, 0]);
+		let distances = crowding_distances(&population, &non_dominated_ranks(&population));
+		assert!(distances[0].is_infinite() && distances[3].is_infinite());
+	}
+
+	#[test]
+	fn test_pick() {
+		let solution = |resources, makespan| { Solution { objectives: objectives(resources, makespan), production: vec![] } };
+		let front = Front {
+			names: vec!["armoire".to_string(), "time".to_string()],
+			solutions: vec![solution(vec![0], 0), solution(vec![1], 50), solution(vec![2], 200)],
+		};
+		assert_eq!(front.pick(&Pick::Index(2)).unwrap().objectives.makespan, 200);
+		assert!(front.pick(&Pick::Index(3)).is_err());
+		assert_eq!(front.pick(&Pick::Weights(vec![1., 0.])).unwrap().objectives.makespan, 200);
+		assert_eq!(front.pick(&Pick::Weights(vec![0., 1.])).unwrap().objectives.makespan, 0);
+		assert_eq!(front.pick(&Pick::Weights(vec![])).unwrap().objectives.makespan, 50);
+		assert!(front.pick(&Pick::Weights(vec![1.])).is_err());
+		let tied = Front { names: front.names.clone(), solutions: front.solutions[..2].to_vec() };
+		assert_eq!(tied.pick(&Pick::Weights(vec![])).unwrap().objectives.makespan, 50);
+	}
+
+	#[test]
+	fn test_front_is_non_dominated() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let config: Config = serde_json::from_str("{ \"generation_size\": 20, \"iterations\": 10, \"max_depth\": 20 }").unwrap();
+		let front = run(simulation, &config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+
+		assert_eq!(front.names, vec!["armoire".to_string(), "time".to_string()]);
+		for a in &front.solutions {
+			assert!(front.solutions.iter().all(|b| { !b.objectives.dominates(&a.objectives) }));
+		}
+		assert!(front.solutions.windows(2).all(|pair| { pair[0].objectives.makespan < pair[1].objectives.makespan }));
+		// Doing nothing is the quickest schedule, an armoire needs two batches
+		assert_eq!(front.solutions[0].objectives.makespan, 0);
+		assert!(front.solutions.iter().any(|solution| { solution.objectives.resources == vec![1] }));
+	}
+}
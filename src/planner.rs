@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::{Simulation, Process, Resource},
+	control::Control,
+	check::manage_resources,
+	inventory::Inventory,
+	solver::{Production, Path, batchify},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	// Quantity to gain of each optimized resource, 1 when missing
+	targets: HashMap<String, usize>,
+	// Scale the targets up as long as the plan stays doable
+	maximize: bool,
+	// Total executions a plan may contain, bounds cycles and long chains
+	max_runs: usize,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			targets: HashMap::new(),
+			maximize: true,
+			max_runs: 10000,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+	// Executions of each process, dependencies first
+	pub runs: Vec<(String, usize)>,
+}
+
+#[derive(Clone)]
+struct State {
+	stock: Inventory,
+	runs: HashMap<String, usize>,
+	order: Vec<String>,
+	total: usize,
+}
+
+fn quantity(resources: &[Resource], name: &str) -> usize {
+	resources
+		.iter()
+		.filter(|resource| { resource.name == name })
+		.map(|resource| { resource.quantity })
+		.sum()
+}
+
+fn net_output(process: &Process, name: &str) -> usize {
+	quantity(&process.output, name).saturating_sub(quantity(&process.input, name))
+}
+
+// Quantity taken at start and given back at the end of an execution
+fn held(process: &Process, name: &str) -> usize {
+	quantity(&process.input, name).min(quantity(&process.output, name))
+}
+
+struct Planner<'a> {
+	simulation: &'a Simulation,
+	max_runs: usize,
+}
+
+impl<'a> Planner<'a> {
+	// Producers of a resource, fewest executions first
+	fn producers(&self, name: &str) -> Vec<&'a Process> {
+		let mut producers: Vec<&Process> = self.simulation.processes
+			.values()
+			.filter(|process| { net_output(process, name) > 0 })
+			.collect();
+		producers.sort_by(|a, b| {
+			net_output(b, name).cmp(&net_output(a, name))
+				.then(a.duration.cmp(&b.duration))
+				.then(a.name.cmp(&b.name))
+		});
+		producers
+	}
+
+	fn demand(&self, name: &str, needed: usize, state: &mut State, stack: &mut Vec<String>) -> Result<(), String> {
+		// Producing may consume some of the resource itself through a cycle, so produce until it's enough
+		loop {
+			let available = *state.stock.get(name).unwrap_or(&0);
+			if available >= needed {
+				state.stock.insert(name.to_string(), available - needed);
+				return Ok(())
+			}
+			// Resources already being produced up the chain can only come from the stock
+			if stack.iter().any(|producing| { producing == name }) {
+				return Err(format!("Cyclic dependency on {}", name))
+			}
+			self.produce_missing(name, needed - available, state, stack)?;
+		}
+	}
+
+	fn produce_missing(&self, name: &str, missing: usize, state: &mut State, stack: &mut Vec<String>) -> Result<(), String> {
+		let producers = self.producers(name);
+		if producers.is_empty() {
+			return Err(format!("Unable to produce {} more {}", missing, name))
+		}
+		let mut last_error = String::new();
+		stack.push(name.to_string());
+		for process in producers {
+			let mut attempt = state.clone();
+			match self.produce(process, name, missing, &mut attempt, stack) {
+				Ok (_) => {
+					*state = attempt;
+					stack.pop();
+					return Ok(())
+				},
+				Err (err) => last_error = err,
+			}
+		}
+		stack.pop();
+		Err(last_error)
+	}
+
+	fn produce(&self, process: &Process, name: &str, missing: usize, state: &mut State, stack: &mut Vec<String>) -> Result<(), String> {
+		let per_run = net_output(process, name);
+		let runs = missing.div_ceil(per_run);
+		state.total += runs;
+		if state.total > self.max_runs {
+			return Err(format!("Plan needs more than {} executions", self.max_runs))
+		}
+		// Held resources are needed once, executions reuse them one after the other
+		for input in &process.input {
+			let held_quantity = held(process, &input.name);
+			let consumed = (input.quantity - held_quantity) * runs;
+			self.demand(&input.name, consumed + held_quantity, state, stack)?;
+			*state.stock.entry(input.name.clone()).or_insert(0) += held_quantity;
+		}
+		for output in &process.output {
+			let produced = (output.quantity - held(process, &output.name)) * runs;
+			*state.stock.entry(output.name.clone()).or_insert(0) += produced;
+		}
+		if !state.runs.contains_key(&process.name) {
+			state.order.push(process.name.clone());
+		}
+		*state.runs.entry(process.name.clone()).or_insert(0) += runs;
+		Ok(())
+	}
+
+	fn plan(&self, targets: &[(String, usize)]) -> Result<Plan, String> {
+		let mut state = State {
+			stock: self.simulation.inventory.clone(),
+			runs: HashMap::new(),
+			order: vec![],
+			total: 0,
+		};
+		for (name, quantity) in targets {
+			let initial = *self.simulation.inventory.get(name).unwrap_or(&0);
+			self.demand(name, initial + quantity, &mut state, &mut vec![])?;
+		}
+		let runs = state.order
+			.iter()
+			.map(|name| { (name.clone(), state.runs[name]) })
+			.collect();
+		Ok(Plan { runs })
+	}
+}
+
+// Executions needed to gain the target quantities over the initial stock
+pub fn plan(simulation: &Simulation, targets: &[(String, usize)], max_runs: usize) -> Result<Plan, String> {
+	let planner = Planner { simulation, max_runs };
+	planner.plan(targets)
+}
+
+// Order the planned executions as soon as they are doable, dependencies first
+pub fn schedule(simulation: &Simulation, plan: &Plan) -> Result<Production, String> {
+	batchify(simulation, schedule_path(simulation, plan)?)
+}
+
+pub fn schedule_path(simulation: &Simulation, plan: &Plan) -> Result<Path, String> {
+	let mut remaining = plan.runs.clone();
+	let mut inventory = simulation.inventory.clone();
+	let mut path: Path = vec![];
+	while remaining.iter().any(|(_, runs)| { *runs > 0 }) {
+		let next = remaining
+			.iter_mut()
+			.filter(|(_, runs)| { *runs > 0 })
+			.find_map(|(name, runs)| {
+				let process = simulation.processes.get(name)?;
+				manage_resources(inventory.clone(), process)
+					.ok()
+					.map(|updated_inventory| {
+						*runs -= 1;
+						(name.clone(), updated_inventory)
+					})
+			});
+		match next {
+			Some ((name, updated_inventory)) => {
+				path.push(name);
+				inventory = updated_inventory;
+			},
+			None => return Err("Unable to schedule the plan, no planned process is doable".to_string()),
+		}
+	}
+	Ok(path)
+}
+
+pub fn solve(simulation: Simulation, config: Config, control: &Control) -> Result<Production, String> {
+	let mut targets: Vec<(String, usize)> = simulation.optimize
+		.iter()
+		.map(|name| { (name.clone(), *config.targets.get(name).unwrap_or(&1)) })
+		.collect();
+	targets.sort();
+	// Any plan would meet empty targets, nor can time be planned for
+	if targets.is_empty() {
+		return Err("The planner needs a resource to optimize, not only time".to_string())
+	}
+	let scheduled = |factor: usize| -> Result<Path, String> {
+		let scaled_targets: Vec<(String, usize)> = targets
+			.iter()
+			.map(|(name, quantity)| {
+				quantity.checked_mul(factor).map(|quantity| { (name.clone(), quantity) }).ok_or(format!("Target of {} too large", name))
+			})
+			.collect::<Result<Vec<(String, usize)>, String>>()?;
+		let scaled_plan = plan(&simulation, &scaled_targets, config.max_runs)?;
+		let path = schedule_path(&simulation, &scaled_plan)?;
+		control.incumbent(&path);
+		Ok(path)
+	};
+	let mut best = scheduled(1)?;
+
+	if config.maximize {
+		// Double the targets until the plan fails, then bisect
+		let (mut low, mut high) = (1, 2);
+		while !control.is_cancelled() {
+			match scheduled(high) {
+				Ok (path) => {
+					best = path;
+					low = high;
+					match high.checked_mul(2) {
+						Some (doubled) => high = doubled,
+						None => break,
+					}
+				},
+				Err (_) => break,
+			}
+		}
+		while high - low > 1 && !control.is_cancelled() {
+			let middle = (low + high) / 2;
+			match scheduled(middle) {
+				Ok (path) => {
+					best = path;
+					low = middle;
+				},
+				Err (_) => high = middle,
+			}
+		}
+	}
+	batchify(&simulation, best)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use crate::ast::parse;
+	use crate::control::Control;
+	use super::{plan, schedule, solve, Config};
+
+	#[test]
+	fn test_ikea_plan() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let targets = vec![("armoire".to_string(), 1)];
+		let armoire_plan = plan(&simulation, &targets, 100).unwrap();
+
+		let mut runs = armoire_plan.runs.clone();
+		runs.sort();
+		assert_eq!(runs, vec![
+			("do_armoire_ikea".to_string(), 1),
+			("do_etagere".to_string(), 3),
+			("do_fond".to_string(), 1),
+			("do_montant".to_string(), 2),
+		]);
+		assert_eq!(armoire_plan.runs.last(), Some(&("do_armoire_ikea".to_string(), 1)));
+
+		let production = schedule(&simulation, &armoire_plan).unwrap();
+		assert_eq!(production.len(), 2);
+		assert_eq!(production[1], (30, vec!["do_armoire_ikea".to_string()]));
+	}
+
+	#[test]
+	fn test_plan_missing_stock() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let targets = vec![("armoire".to_string(), 2)];
+
+		assert!(plan(&simulation, &targets, 100).is_err());
+	}
+
+	#[test]
+	fn test_time_only_simulation() {
+		let simulation = parse("euro:10\nachat:(euro:1):(materiel:1):10\noptimize:(time)\n".to_string()).unwrap();
+		assert!(solve(simulation, Config::default(), &Control::default()).is_err());
+	}
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration as WallDuration, Instant};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	score::{Score, Scorer, BroScore, Values},
+	solver::{Algorithm, Production, Path, Duration, batchify, flatten, solve_with_config},
+};
+
+// Time given to the members to hand back their best path once the deadline is passed
+const GRACE_PERIOD: WallDuration = WallDuration::from_millis(1000);
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	// Algorithms run side by side, a name can be repeated
+	members: Vec<String>,
+	// milliseconds shared by all the members
+	time_limit: u64,
+	// Used to compare the members' productions
+	time_weight: f32,
+	// Steps of the horizon the members' makespans are weighed against without a delay
+	max_depth: usize,
+	// leo or hugo
+	scorer: BroScore,
+	// Value function of some resources, linear for the others
+	values: Values,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			members: vec![
+				"genetic".to_string(),
+				"genetic".to_string(),
+				"mcts".to_string(),
+				"annealing".to_string(),
+				"tabu".to_string(),
+				"greedy".to_string(),
+				"beam".to_string(),
+				"planner".to_string(),
+			],
+			time_limit: 10000,
+			time_weight: 0.1,
+			max_depth: 100,
+			scorer: BroScore::default(),
+			values: Values::new(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+	Done,
+	// Returned its best path when cancelled or out of time
+	Stopped,
+	Failed(String),
+	TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+	pub name: String,
+	// Replays the member alone with --seed
+	pub seed: u64,
+	pub status: Status,
+	pub score: Option<Score>,
+	pub makespan: Option<Duration>,
+	pub elapsed: WallDuration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+	pub reports: Vec<Report>,
+	pub best: Option<usize>,
+}
+
+// Best production found so far by any member
+#[derive(Debug, Clone)]
+pub struct Incumbent {
+	pub score: Score,
+	pub member: usize,
+	pub path: Path,
+	pub production: Production,
+}
+
+pub type SharedIncumbent = Arc<Mutex<Option<Incumbent>>>;
+
+fn makespan(production: &Production) -> Duration {
+	production.iter().map(|(duration, _)| { duration }).sum()
+}
+
+// Keep the production if it beats the current incumbent
+fn offer(incumbent: &SharedIncumbent, member: usize, score: Score, path: &Path, production: &Production) -> bool {
+	match incumbent.lock() {
+		Ok (mut incumbent) => {
+			let better = match &*incumbent {
+				Some (current) => score > current.score,
+				None => true,
+			};
+			if better {
+				*incumbent = Some(Incumbent { score, member, path: path.clone(), production: production.clone() });
+			}
+			better
+		},
+		Err (_) => false,
+	}
+}
+
+pub fn run(simulation: Simulation, config: Config, config_content: &str, control: &Control, rng: &mut StdRng) -> Result<(Production, Summary), String> {
+	let algorithms = config.members
+		.iter()
+		.map(|name| {
+			match name.parse()? {
+				Algorithm::Portfolio => Err("A portfolio can't be a member of a portfolio".to_string()),
+				algorithm => Ok(algorithm),
+			}
+		})
+		.collect::<Result<Vec<Algorithm>, String>>()?;
+	if algorithms.is_empty() {
+		return Err("The portfolio has no member".to_string())
+	}
+
+	let start = Instant::now();
+	let time_limit = WallDuration::from_millis(config.time_limit);
+	// Members stop with the caller, at the deadline, or when the portfolio stops waiting for them
+	let token = control.token.child().with_time_limit(time_limit);
+	let incumbent: SharedIncumbent = Arc::new(Mutex::new(None));
+	let seeds: Vec<u64> = algorithms.iter().map(|_| { rng.gen() }).collect();
+	let (sender, receiver) = mpsc::channel();
+	let mut handles: Vec<JoinHandle<()>> = vec![];
+	for (member, algorithm) in algorithms.into_iter().enumerate() {
+		let mut member_rng = StdRng::seed_from_u64(seeds[member]);
+		let sender = sender.clone();
+		let simulation = simulation.clone();
+		let config_content = config_content.to_string();
+		let incumbent = Arc::clone(&incumbent);
+		let caller = control.clone();
+		let time_weight = config.time_weight;
+		let max_depth = config.max_depth;
+		let bro_score = config.scorer;
+		let values = config.values.clone();
+		let token = token.clone();
+		handles.push(thread::spawn(move || {
+			let scorer = Scorer::new(simulation.clone(), time_weight, bro_score).with_values(values).with_horizon(max_depth);
+			let best_known = Arc::clone(&incumbent);
+			// Best path of this member, its final production may not be the best it found
+			let member_best: Arc<Mutex<Option<(Score, Production)>>> = Arc::new(Mutex::new(None));
+			let offer_path = {
+				let member_best = Arc::clone(&member_best);
+				Arc::new(move |path: &Path| -> Result<(), String> {
+					let score = scorer.score(path)?;
+					let production = batchify(scorer.simulation(), path.clone())?;
+					if offer(&incumbent, member, score, path, &production) {
+						caller.incumbent(path);
+					}
+					let mut member_best = member_best.lock().map_err(|_| { "Poisoned member lock".to_string() })?;
+					if member_best.as_ref().is_none_or(|(best_score, _)| { score > *best_score }) {
+						*member_best = Some((score, production));
+					}
+					Ok(())
+				})
+			};
+			// Members able to carry on from a path start from the best one of the others
+			let member_control = {
+				let offer_path = Arc::clone(&offer_path);
+				Control::new(token.clone())
+					.on_incumbent(Arc::new(move |path: &Path| { offer_path(path).ok(); }))
+					.with_best_known(Arc::new(move || {
+						best_known.lock().ok()?.as_ref().map(|incumbent| { incumbent.path.clone() })
+					}))
+			};
+			let result = solve_with_config(simulation, algorithm, &config_content, &member_control, &mut member_rng)
+				.and_then(|production| { offer_path(&flatten(&production)) })
+				.and_then(|_| {
+					member_best
+						.lock()
+						.map_err(|_| { "Poisoned member lock".to_string() })?
+						.take()
+						.ok_or("No production found".to_string())
+				});
+			// The portfolio may have stopped listening
+			sender.send((member, result, start.elapsed(), token.is_cancelled())).ok();
+		}));
+	}
+	drop(sender);
+
+	let mut reports: Vec<Report> = config.members
+		.iter()
+		.enumerate()
+		.map(|(member, name)| {
+			Report {
+				name: format!("{}#{}", name, member),
+				seed: seeds[member],
+				status: Status::TimedOut,
+				score: None,
+				makespan: None,
+				elapsed: time_limit,
+			}
+		})
+		.collect();
+	while let Some (remaining) = (time_limit + GRACE_PERIOD).checked_sub(start.elapsed()) {
+		match receiver.recv_timeout(remaining) {
+			Ok ((member, result, elapsed, stopped)) => {
+				let report = &mut reports[member];
+				report.elapsed = elapsed;
+				match result {
+					Ok ((score, production)) => {
+						report.status = if stopped { Status::Stopped } else { Status::Done };
+						report.score = Some(score);
+						report.makespan = Some(makespan(&production));
+					},
+					Err (err) => report.status = Status::Failed(err),
+				}
+			},
+			Err (_) => break,
+		}
+	}
+	// Members still running are reported as timed out, and waited for so none outlives the portfolio
+	token.cancel();
+	for handle in handles {
+		handle.join().ok();
+	}
+
+	let best = incumbent
+		.lock()
+		.map_err(|_| { "A portfolio member panicked".to_string() })?
+		.clone()
+		.ok_or("No portfolio member found a production in time")?;
+	let summary = Summary {
+		reports,
+		best: Some(best.member),
+	};
+	Ok((best.production, summary))
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{:<16} {:<10} {:>10} {:>10} {:>10} {:>20}", "member", "status", "score", "makespan", "time (s)", "seed")?;
+		for (member, report) in self.reports.iter().enumerate() {
+			let status = match &report.status {
+				Status::Failed(_) => "failed",
+				_ if self.best == Some(member) => "best",
+				Status::Done => "done",
+				Status::Stopped => "stopped",
+				Status::TimedOut => "timed out",
+			};
+			let score = report.score.map(|score| { score.to_string() }).unwrap_or("-".to_string());
+			let makespan = report.makespan.map(|makespan| { makespan.to_string() }).unwrap_or("-".to_string());
+			writeln!(f, "{:<16} {:<10} {:>10} {:>10} {:>10.3} {:>20}", report.name, status, score, makespan, report.elapsed.as_secs_f64(), report.seed)?;
+			if let Status::Failed(err) = &report.status {
+				writeln!(f, "  {}", err)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::ast::parse;
+	use crate::control::Control;
+	use crate::score::Scorer;
+	use crate::solver::flatten;
+	use super::{run, Config, Status};
+
+	fn config(members: &[&str]) -> Config {
+		Config {
+			members: members.iter().map(|member| { member.to_string() }).collect(),
+			..Config::default()
+		}
+	}
+
+	#[test]
+	fn test_best_member_wins() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let config = config(&["greedy", "annealing", "planner"]);
+		let scorer = Scorer::new(simulation.clone(), config.time_weight, config.scorer).with_horizon(config.max_depth);
+		let (production, summary) = run(simulation, config, "{}", &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+
+		assert!(summary.reports.iter().all(|report| { report.status == Status::Done }));
+		let best_score = summary.reports.iter().filter_map(|report| { report.score }).max();
+		let best = summary.best.unwrap();
+		assert_eq!(summary.reports[best].score, best_score);
+		assert_eq!(Some(scorer.score(&flatten(&production)).unwrap()), best_score);
+	}
+
+	#[test]
+	fn test_failed_member_is_reported() {
+		// The planner needs a resource to optimize
+		let simulation = parse("euro:10\nachat:(euro:1):(materiel:1):10\noptimize:(time)\n".to_string()).unwrap();
+		let (_, summary) = run(simulation, config(&["greedy", "planner"]), "{}", &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+
+		assert_eq!(summary.reports[0].status, Status::Done);
+		assert!(matches!(summary.reports[1].status, Status::Failed(_)));
+		assert_eq!(summary.best, Some(0));
+	}
+}
@@ -6,11 +6,236 @@ use super::{
     ScoreMap,
 };
 
+// Produced minus consumed quantity of each resource, per execution. Resources given back
+// by the process, such as machines, net to zero
+fn net_quantities(process: &ast::Process) -> Vec<(String, f64)> {
+	let mut net: Vec<(String, f64)> = vec![];
+	let mut add = |name: &String, quantity: f64| {
+		match net.iter_mut().find(|(other, _)| { other == name }) {
+			Some ((_, total)) => *total += quantity,
+			None => net.push((name.clone(), quantity)),
+		}
+	};
+	for resource in &process.output {
+		add(&resource.name, resource.quantity as f64);
+	}
+	for resource in &process.input {
+		add(&resource.name, -(resource.quantity as f64));
+	}
+	net
+}
+
+// Value of a unit of an optimized resource, as with leo
+const TARGET_VALUE: f64 = 1000.;
+// Chance a conversion still to do gets done before the end of the schedule. A unit is worth what
+// it can become times this chance, so each conversion towards the optimized resources raises the
+// score. The scorer doesn't know how long the schedule runs, so it takes an even chance
+const DISCOUNT: f64 = 0.5;
+
+fn consumed(net: &[(String, f64)]) -> impl Iterator<Item = (&String, f64)> {
+	net.iter().filter(|(_, quantity)| { *quantity < 0. }).map(|(name, quantity)| { (name, -quantity) })
+}
+
+// Base resources a unit of each resource is made of, base resources being the optimized ones
+// and the ones no process produces. The cheapest way to make a resource counts
+fn contents(simulation: &Simulation, processes: &[Vec<(String, f64)>]) -> HashMap<String, f64> {
+	let produced = |name: &String| { processes.iter().flatten().any(|(other, quantity)| { other == name && *quantity > 0. }) };
+	let mut contents: HashMap<String, f64> = processes
+		.iter()
+		.flatten()
+		.map(|(name, _)| {
+			let base = simulation.optimize.contains(name) || !produced(name);
+			(name.clone(), if base { 1. } else { f64::INFINITY })
+		})
+		.collect();
+	for _ in 0..=contents.len() {
+		let mut changed = false;
+		for net in processes {
+			let content: f64 = consumed(net).map(|(name, quantity)| { quantity * contents[name] }).sum();
+			let produced: f64 = net.iter().filter(|(_, quantity)| { *quantity > 0. }).map(|(_, quantity)| { quantity }).sum();
+			for (name, quantity) in net {
+				if *quantity > 0. && contents[name] > content / produced {
+					contents.insert(name.clone(), content / produced);
+					changed = true;
+				}
+			}
+		}
+		if !changed {
+			break
+		}
+	}
+	contents
+}
+
+// Value of the optimized resources a unit of each resource costs, the cheapest way to make it. Resources
+// made out of anything else, or out of nothing, have no cost
+fn costs(simulation: &Simulation, processes: &[Vec<(String, f64)>]) -> HashMap<String, f64> {
+	let mut costs: HashMap<String, f64> = simulation.optimize
+		.iter()
+		.map(|name| { (name.clone(), TARGET_VALUE) })
+		.collect();
+	for _ in 0..=processes.len() {
+		let mut changed = false;
+		for net in processes {
+			let cost: Option<f64> = consumed(net).map(|(name, quantity)| { costs.get(name).map(|cost| { quantity * cost }) }).sum();
+			let produced: f64 = net.iter().filter(|(_, quantity)| { *quantity > 0. }).map(|(_, quantity)| { quantity }).sum();
+			let cost = match cost {
+				Some (cost) if cost > 0. && produced > 0. => cost / produced,
+				_ => continue,
+			};
+			for (name, quantity) in net {
+				if *quantity > 0. && !simulation.optimize.contains(name) && costs.get(name).is_none_or(|other| { *other > cost }) {
+					costs.insert(name.clone(), cost);
+					changed = true;
+				}
+			}
+		}
+		if !changed {
+			break
+		}
+	}
+	costs.retain(|name, _| { !simulation.optimize.contains(name) });
+	costs
+}
+
+// Value of a unit of each resource. A unit of any resource that isn't optimized gets the best share
+// of what a process turns it into: the discounted value of the process's outputs, split between
+// the consumed units by the base resources they are made of, and no more than its discounted cost
 pub fn build_score_map_hugo(simulation: &Simulation, _weight_multiplier: usize) -> ScoreMap {
-    unimplemented!();
-    // let score_map: ScoreMap = simulation.processes.iter().map(|p| (p, 0)).
+	let mut values: HashMap<String, f64> = simulation.optimize
+		.iter()
+		.map(|name| { (name.clone(), TARGET_VALUE) })
+		.collect();
+	// Sorted so the values don't depend on the HashMap order
+	let mut processes: Vec<&ast::Process> = simulation.processes.values().collect();
+	processes.sort_by(|a, b| { a.name.cmp(&b.name) });
+	let processes: Vec<Vec<(String, f64)>> = processes.into_iter().map(net_quantities).collect();
+	let contents = contents(simulation, &processes);
+	// Turning a unit made out of optimized resources back into them is a conversion like any other,
+	// so it's worth at most its chance of their cost: buying never looks better than keeping them
+	let costs = costs(simulation, &processes);
+	let cap = |name: &String| { costs.get(name).map_or(f64::INFINITY, |cost| { DISCOUNT * cost }) };
+	// Units no process makes from base resources, or made for free, weigh as much as the others
+	let content = |name: &String| { Some(contents[name]).filter(|content| { content.is_finite() && *content > 0. }).unwrap_or(1.) };
+	// Values flow back one process per round, like the longest paths of Bellman-Ford. The rounds
+	// are bounded as a loop turning a resource into more of itself would never settle
+	for _ in 0..=contents.len() {
+		let mut changed = false;
+		for net in &processes {
+			let produced: f64 = net.iter()
+				.filter(|(_, quantity)| { *quantity > 0. })
+				.map(|(name, quantity)| { quantity * values.get(name).unwrap_or(&0.) })
+				.sum();
+			let consumed_content: f64 = consumed(net).map(|(name, quantity)| { quantity * content(name) }).sum();
+			if produced <= 0. || consumed_content <= 0. {
+				continue
+			}
+			for (name, _) in consumed(net) {
+				let share = (DISCOUNT * produced * content(name) / consumed_content).min(cap(name));
+				if !simulation.optimize.contains(name) && values.get(name).is_none_or(|value| { *value < share }) {
+					values.insert(name.clone(), share);
+					changed = true;
+				}
+			}
+		}
+		if !changed {
+			break
+		}
+	}
+	// Rounded down, so buying never gains from the rounding
+	values
+		.into_iter()
+		.map(|(name, value)| { (name, value.floor().min(Score::MAX as f64) as Score) })
+		.collect()
 }
 
-pub fn hugo_score() -> Result<Score, String> {
-    unimplemented!();
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::fs::read_to_string;
+
+	use crate::ast::{self, parse};
+	use crate::score::value::inventory_score;
+	use super::{build_score_map_hugo, consumed, costs, net_quantities, Score, ScoreMap};
+
+	const FILES: [&str; 6] = ["ikea", "inception", "pomme", "recre", "simple", "steak"];
+
+	fn value(score_map: &ScoreMap, name: &String) -> f64 {
+		*score_map.get(name).unwrap_or(&0) as f64
+	}
+
+	fn processes(simulation: &ast::Simulation) -> Vec<Vec<(String, f64)>> {
+		simulation.processes.values().map(net_quantities).collect()
+	}
+
+	#[test]
+	fn test_a_unit_is_worth_at_most_what_it_becomes() {
+		for name in &FILES {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			let score_map = build_score_map_hugo(&simulation, 100);
+			for (resource, unit) in &score_map {
+				if simulation.optimize.contains(resource) || *unit == 0 {
+					continue
+				}
+				// Some process turns it into outputs worth at least what it consumes of it
+				let converted = processes(&simulation).iter().any(|net| {
+					let outputs: f64 = net.iter().filter(|(_, quantity)| { *quantity > 0. }).map(|(name, quantity)| { quantity * value(&score_map, name) }).sum();
+					consumed(net).any(|(name, quantity)| { name == resource && quantity * *unit as f64 <= outputs })
+				});
+				assert!(converted, "{} {}", name, resource);
+			}
+		}
+	}
+
+	#[test]
+	fn test_a_unit_is_worth_at_most_its_cheapest_production() {
+		for name in &FILES {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			let score_map = build_score_map_hugo(&simulation, 100);
+			let processes = processes(&simulation);
+			let costs = costs(&simulation, &processes);
+			let cost = |resource: &String| -> Option<f64> {
+				if simulation.optimize.contains(resource) { Some(value(&score_map, resource)) } else { costs.get(resource).cloned() }
+			};
+			for (resource, unit) in &score_map {
+				if let Some (cost) = costs.get(resource) {
+					assert!(*unit as f64 <= *cost, "{} {}: {} > {}", name, resource, unit, cost);
+				}
+			}
+			// No process makes a unit for less than its cost, up to the rounds the costs settle in
+			for net in &processes {
+				let spent: Option<f64> = consumed(net).map(|(name, quantity)| { cost(name).map(|cost| { quantity * cost }) }).sum();
+				let produced: f64 = net.iter().filter(|(_, quantity)| { *quantity > 0. }).map(|(_, quantity)| { quantity }).sum();
+				if let Some (spent) = spent.filter(|spent| { *spent > 0. }) {
+					for (resource, quantity) in net.iter().filter(|(name, quantity)| { *quantity > 0. && !simulation.optimize.contains(name) }) {
+						assert!(costs[resource] * produced <= spent * 1.001, "{} {} {}", name, resource, quantity);
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_hugo_score_map() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let score_map = build_score_map_hugo(&simulation, 100);
+		// Each conversion raises the value, split by the planks each part is made of
+		assert!(score_map["armoire"] > score_map["fond"] && score_map["fond"] > score_map["montant"] && score_map["montant"] > score_map["planche"]);
+		assert_eq!(score_map["montant"], score_map["etagere"]);
+		assert!((score_map["fond"] - 2 * score_map["montant"]).abs() <= 1);
+
+		let simulation = parse(read_to_string("ressources/pomme").unwrap()).unwrap();
+		let score_map = build_score_map_hugo(&simulation, 100);
+		// Bought 2000 for 100 euros, worth less than the euros until it's sold
+		assert!(score_map["lait"] * 2000 < score_map["euro"] * 100);
+		assert!(score_map["tarte_pomme"] > score_map["pate_sablee"]);
+		// The oven is given back, it isn't converted into anything
+		assert_eq!(score_map.get("four"), None);
+		let inventory: HashMap<String, usize> = vec![("euro".to_string(), 3), ("boite".to_string(), 1)].into_iter().collect();
+		let stock = 3 * score_map["euro"] + score_map["boite"];
+		assert_eq!(inventory_score(&score_map, &HashMap::new(), 0.1, inventory.clone(), None), stock);
+		// A tenth of the delay spent, with a time weight of 0.1: 1% off the stock, and 90% of the time score
+		let timed = (stock as f64 * 0.99 + 1000. * 0.1 * 0.9).round() as Score;
+		assert_eq!(inventory_score(&score_map, &HashMap::new(), 0.1, inventory, Some(0.1)), timed);
+	}
 }
@@ -2,8 +2,6 @@ use std::collections::HashMap;
 use std::cmp::max;
 
 use crate::ast::{self, Simulation};
-use crate::solver::{Path};
-use crate::simulate::simulate;
 use super::{
     Score,
     ScoreMap,
@@ -12,7 +10,10 @@ use super::{
 fn find_dependencies(simulation: &Simulation, resource_name: &String) -> Option<Vec<ast::Resource>> {
 	let mut dependencies: Vec<ast::Resource> = vec![];
 
-	for (_, process) in &simulation.processes {
+	// Sorted so the weights don't depend on the HashMap order
+	let mut processes: Vec<&ast::Process> = simulation.processes.values().collect();
+	processes.sort_by(|a, b| { a.name.cmp(&b.name) });
+	for process in processes {
 		for resource in &process.output {
 			if resource.name == *resource_name {
 				for resource_needed in &process.input {
@@ -26,9 +27,11 @@ fn find_dependencies(simulation: &Simulation, resource_name: &String) -> Option<
 	if dependencies.is_empty() { None } else { Some(dependencies) }
 }
 
+// Weight of a single unit: scores are multiplied by the quantities held, weighting the units
+// needed by a recipe too would make a pile of cheap inputs worth more than what it was bought with
 fn update_score_map(map: &mut ScoreMap, resource: &ast::Resource, weight: usize) {
 	let existing_score = map.get(&resource.name).unwrap_or(&0);
-	let resource_score = weight as Score * resource.quantity as Score;
+	let resource_score = weight as Score;
 	let score = max(existing_score, &resource_score);
 	map.insert(resource.name.clone(), *score);
 }
@@ -71,18 +74,36 @@ pub fn build_score_map_leo(simulation: &Simulation, _weight_multiplier: usize) -
 		// TODO: unmock value
 		update_score_map(&mut score_map, &resource, 1000);
 	}
+	cap_optimized_inputs(simulation, &mut score_map);
 	score_map
 }
 
-pub fn leo_score(simulation: &Simulation, score_map: &ScoreMap, time_weight: f32, path: &Path) -> Result<Score, String> {
-	let (inventory, duration) = simulate(&simulation, &path, false)?;
-	let stock_score =
-		inventory
-		.into_iter()
-		.fold(0, |score, (name, _)| {
-			score + *score_map.get(&name).unwrap_or(&0)
-		});
-	let time_score = duration as f32 * time_weight;
-	let score = stock_score as Score - time_score.round() as Score;
-	Ok(score)
+// Units turned into an optimized resource are worth at most their share of what they make,
+// so making it never lowers the score. Resources given back, such as machines, aren't capped
+fn cap_optimized_inputs(simulation: &Simulation, score_map: &mut ScoreMap) {
+	let mut processes: Vec<&ast::Process> = simulation.processes.values().collect();
+	processes.sort_by(|a, b| { a.name.cmp(&b.name) });
+	let quantity = |resources: &[ast::Resource], name: &String| -> Score {
+		resources.iter().filter(|resource| { resource.name == *name }).map(|resource| { resource.quantity as Score }).sum()
+	};
+	for process in processes {
+		if !process.output.iter().any(|output| { simulation.optimize.contains(&output.name) }) {
+			continue
+		}
+		let value: Score = process.output
+			.iter()
+			.map(|output| { score_map.get(&output.name).unwrap_or(&0).saturating_mul(output.quantity as Score) })
+			.fold(0, Score::saturating_add);
+		let consumed: Vec<(&String, Score)> = process.input
+			.iter()
+			.map(|input| { (&input.name, quantity(&process.input, &input.name) - quantity(&process.output, &input.name)) })
+			.filter(|(name, quantity)| { *quantity > 0 && !simulation.optimize.contains(name) })
+			.collect();
+		let units: Score = consumed.iter().map(|(_, quantity)| { quantity }).sum();
+		for (name, _) in consumed {
+			if let Some (weight) = score_map.get_mut(name) {
+				*weight = (*weight).min(value / units);
+			}
+		}
+	}
 }
@@ -1,21 +1,40 @@
 mod leo;
 mod hugo;
+mod value;
 
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
 
 use crate::ast::Simulation;
-use crate::solver::{Path};
-use leo::{build_score_map_leo, leo_score};
-use hugo::{build_score_map_hugo, hugo_score};
+use crate::inventory::Inventory;
+use crate::simulate::PrefixCache;
+use crate::solver::{Path, makespan};
+use leo::build_score_map_leo;
+use hugo::build_score_map_hugo;
+use value::inventory_score;
+
+pub use value::{ValueFunction, Values};
 
 pub type Score = i32;
 pub type Weight = usize;
 pub type ScoreMap = HashMap<String, Score>;
 
-#[derive(Clone, Copy)] // Remove 'Copy' if you change the enum
+// Entries kept by the caches, fewer scores as each keeps its whole path
+const SCORE_CACHE_CAPACITY: usize = 100_000;
+const PREFIX_CACHE_CAPACITY: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)] // Remove 'Copy' if you change the enum
+#[serde(rename_all = "lowercase")]
 pub enum BroScore {
+	// Resources weighted by how deep they are in the optimized resources' dependencies
+	#[default]
 	Leo,
-    Hugo,
+	// Resources valued by the optimized resources they can be turned into
+	Hugo,
 }
 
 fn build_score_map(simulation: &Simulation, weight_multiplier: usize, broScore: BroScore) -> ScoreMap {
@@ -25,11 +44,49 @@ fn build_score_map(simulation: &Simulation, weight_multiplier: usize, broScore:
 	}
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct CacheStats {
+	pub score_hits: usize,
+	pub score_misses: usize,
+	// Steps read from the prefix trie instead of simulated
+	pub reused_steps: usize,
+	pub simulated_steps: usize,
+}
+
+impl CacheStats {
+	pub fn add(&mut self, other: &CacheStats) {
+		self.score_hits += other.score_hits;
+		self.score_misses += other.score_misses;
+		self.reused_steps += other.reused_steps;
+		self.simulated_steps += other.simulated_steps;
+	}
+}
+
+fn percentage(part: usize, total: usize) -> f64 {
+	if total == 0 { 0. } else { part as f64 * 100. / total as f64 }
+}
+
+impl fmt::Display for CacheStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let scores = self.score_hits + self.score_misses;
+		let steps = self.reused_steps + self.simulated_steps;
+		writeln!(f, "score cache: {} hits / {} ({:.1}%)", self.score_hits, scores, percentage(self.score_hits, scores))?;
+		writeln!(f, "prefix cache: {} steps reused / {} ({:.1}%)", self.reused_steps, steps, percentage(self.reused_steps, steps))
+	}
+}
+
 pub struct Scorer {
 	simulation: Simulation,
 	score_map: ScoreMap,
 	time_weight: f32,
-    bro_score: BroScore,
+	// Time the makespan is weighed against when the simulation has no delay
+	horizon: Option<usize>,
+	values: Values,
+	// Scores by path, a hash alone could collide
+	scores: Mutex<ScoreCache>,
+	score_hits: AtomicUsize,
+	score_misses: AtomicUsize,
+	prefixes: PrefixCache,
 }
 
 impl Scorer {
@@ -39,15 +96,235 @@ impl Scorer {
 			simulation: simulation.clone(),
 			score_map,
 			time_weight: if simulation.optimize_time { time_weight } else { 0. },
-            bro_score,
+			horizon: None,
+			values: Values::new(),
+			scores: Mutex::new(ScoreCache::new(SCORE_CACHE_CAPACITY)),
+			score_hits: AtomicUsize::new(0),
+			score_misses: AtomicUsize::new(0),
+			prefixes: PrefixCache::new(&simulation, PREFIX_CACHE_CAPACITY),
+		}
+	}
+
+	pub fn with_values(self, values: Values) -> Self {
+		Self {
+			values,
+			..self
+		}
+	}
+
+	// Without a delay, makespans are weighed against the longest schedule of max_depth steps,
+	// each one waiting for the one before
+	pub fn with_horizon(self, max_depth: usize) -> Self {
+		let longest = self.simulation.processes.values().map(|process| { process.duration }).max().unwrap_or(0);
+		Self {
+			horizon: Some(max_depth.saturating_mul(longest)),
+			..self
+		}
+	}
+
+	pub fn simulation(&self) -> &Simulation {
+		&self.simulation
+	}
+
+	// Inventory at the end of the path, reusing the simulation of the paths scored before
+	pub fn inventory(&self, path: &Path) -> Result<Inventory, String> {
+		self.prefixes.inventory(&self.simulation, path)
+	}
+
+	pub fn cache_stats(&self) -> CacheStats {
+		let (reused_steps, simulated_steps) = self.prefixes.counts();
+		CacheStats {
+			score_hits: self.score_hits.load(Ordering::Relaxed),
+			score_misses: self.score_misses.load(Ordering::Relaxed),
+			reused_steps,
+			simulated_steps,
+		}
+	}
+
+	// Score of the inventory a path ends with, for paths simulated by the caller. Their
+	// makespan isn't known, time isn't scored
+	pub fn score_inventory(&self, inventory: Inventory) -> Result<Score, String> {
+		Ok(inventory_score(&self.score_map, &self.values, self.time_weight, inventory, None))
+	}
+
+	// Share of the delay, or of the horizon without one, the schedule of the path takes. None when
+	// time isn't optimized or there's nothing to weigh it against
+	fn elapsed(&self, path: &Path) -> Result<Option<f64>, String> {
+		match self.simulation.delay.or(self.horizon) {
+			Some (delay) if delay > 0 && self.time_weight > 0. => {
+				Ok(Some(makespan(&self.simulation, path)? as f64 / delay as f64))
+			},
+			_ => Ok(None),
 		}
 	}
 
-	// TODO: memoize
 	pub fn score(&self, path: &Path) -> Result<Score, String> {
-        match self.bro_score {
-            BroScore::Leo => leo_score(&self.simulation, &self.score_map, self.time_weight, path),
-            BroScore::Hugo => hugo_score(),
-        }
+		let poisoned = |_| { "Poisoned score cache".to_string() };
+		if let Some (score) = self.scores.lock().map_err(poisoned)?.get(path) {
+			self.score_hits.fetch_add(1, Ordering::Relaxed);
+			return Ok(score)
+		}
+		self.score_misses.fetch_add(1, Ordering::Relaxed);
+		let inventory = self.inventory(path)?;
+		let score = inventory_score(&self.score_map, &self.values, self.time_weight, inventory, self.elapsed(path)?);
+		self.scores.lock().map_err(poisoned)?.insert(path.clone(), score);
+		Ok(score)
+	}
+}
+
+// Scores of the paths scored lately, in two halves of the capacity. A full recent half becomes
+// the old one, whose entries move back to the recent half when read again, so only the ones
+// left unread for a whole half are dropped
+struct ScoreCache {
+	recent: HashMap<Path, Score>,
+	old: HashMap<Path, Score>,
+	capacity: usize,
+}
+
+impl ScoreCache {
+	fn new(capacity: usize) -> Self {
+		Self {
+			recent: HashMap::new(),
+			old: HashMap::new(),
+			capacity,
+		}
+	}
+
+	fn get(&mut self, path: &Path) -> Option<Score> {
+		if let Some (score) = self.recent.get(path) {
+			return Some(*score)
+		}
+		let (path, score) = self.old.remove_entry(path)?;
+		self.insert(path, score);
+		Some(score)
+	}
+
+	fn insert(&mut self, path: Path, score: Score) {
+		if self.recent.len() >= self.capacity / 2 {
+			self.old = std::mem::take(&mut self.recent);
+		}
+		self.recent.insert(path, score);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use crate::ast::parse;
+	use crate::check::manage_resources;
+	use crate::inventory::Inventory;
+	use super::{BroScore, ScoreCache, Scorer, Simulation, ValueFunction, Values};
+
+	#[test]
+	fn test_more_of_an_optimized_resource_scores_higher() {
+		let functions = [ValueFunction::Linear, ValueFunction::Logarithmic, ValueFunction::Capped { cap: 50 }];
+		for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			for bro_score in &[BroScore::Leo, BroScore::Hugo] {
+				for function in &functions {
+					for resource in &simulation.optimize {
+						let values: Values = vec![(resource.clone(), *function)].into_iter().collect();
+						let scorer = Scorer::new(simulation.clone(), 0.1, *bro_score).with_values(values);
+						let score = |quantity: usize| {
+							let mut inventory = simulation.inventory.clone();
+							inventory.insert(resource.clone(), quantity);
+							scorer.score_inventory(inventory).unwrap()
+						};
+						for quantity in &[0, 1, 10, 49, 50] {
+							let (less, more) = (score(*quantity), score(quantity + 1));
+							let capped = matches!(function, ValueFunction::Capped { cap } if quantity + 1 > *cap);
+							assert!(more > less || (capped && more == less), "{} {} {:?} {}: {} <= {}", name, resource, function, quantity, more, less);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_making_an_optimized_resource_scores_higher() {
+		// Hugo values a unit by its best use, turning it into less through another process
+		// rightly lowers its score
+		for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+			let simulation = parse(read_to_string(format!("ressources/{}", name)).unwrap()).unwrap();
+			let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo);
+			let processes = simulation.processes
+				.values()
+				.filter(|process| { process.output.iter().any(|output| { simulation.optimize.contains(&output.name) }) });
+			for process in processes {
+				// Just the inputs of the process, then what it turns them into
+				let mut inputs = Inventory::new();
+				for input in &process.input {
+					*inputs.entry(input.name.clone()).or_insert(0) += input.quantity;
+				}
+				let outputs = manage_resources(inputs.clone(), process).unwrap();
+				let (before, after) = (scorer.score_inventory(inputs).unwrap(), scorer.score_inventory(outputs).unwrap());
+				assert!(after >= before, "{} {}: {} < {}", name, process.name, after, before);
+			}
+		}
+	}
+
+	#[test]
+	fn test_quantities_count() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo);
+		let armoires = |quantity: usize| {
+			let inventory = vec![("armoire".to_string(), quantity)].into_iter().collect();
+			scorer.score_inventory(inventory).unwrap()
+		};
+		assert_eq!(armoires(0), 0);
+		assert_eq!(armoires(1000), 1000 * armoires(1));
+		// Resources that ran out are worth nothing
+		let empty = simulation.inventory.keys().map(|name| { (name.clone(), 0) }).collect();
+		assert_eq!(scorer.score_inventory(empty).unwrap(), 0);
+	}
+
+	#[test]
+	fn test_makespan_share_of_the_delay() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let path: Vec<String> = ["do_montant", "do_montant", "do_fond", "do_etagere", "do_etagere", "do_etagere", "do_armoire_ikea"]
+			.iter()
+			.map(|step| { step.to_string() })
+			.collect();
+		let score = |simulation: &Simulation| { Scorer::new(simulation.clone(), 0.1, BroScore::Leo).score(&path).unwrap() };
+		// The parts are made side by side in 20 cycles, then the armoire in 30
+		assert_eq!(score(&simulation), 1000);
+		// 10% off the stock at the delay, with no time left
+		assert_eq!(score(&simulation.clone().with_delay(50)), 900);
+		// 1% off the stock, and 90% of the time score
+		assert_eq!(score(&simulation.clone().with_delay(500)), 1080);
+		// Without a delay, against 10 steps of the longest process: 300 cycles
+		let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo).with_horizon(10);
+		assert_eq!(scorer.score(&path).unwrap(), 1067);
+		let without_time = Simulation { optimize_time: false, ..simulation.with_delay(50) };
+		assert_eq!(score(&without_time), 1000);
+	}
+
+	#[test]
+	fn test_time_only_simulation() {
+		let simulation = parse("euro:2\nachat:(euro:1):(materiel:1):10\noptimize:(time)\n".to_string()).unwrap().with_delay(100);
+		let scorer = Scorer::new(simulation, 0.1, BroScore::Leo);
+		let path = |steps: usize| { vec!["achat".to_string(); steps] };
+		// Both buys run side by side
+		assert_eq!(scorer.score(&path(2)).unwrap(), scorer.score(&path(1)).unwrap());
+		assert!(scorer.score(&path(0)).unwrap() > scorer.score(&path(1)).unwrap());
+	}
+
+	#[test]
+	fn test_score_cache_keeps_the_scores_read_lately() {
+		let mut cache = ScoreCache::new(4);
+		let path = |step: usize| { vec![step.to_string()] };
+		cache.insert(path(0), 0);
+		cache.insert(path(1), 1);
+		// Fills the recent half, which becomes the old one
+		cache.insert(path(2), 2);
+		assert_eq!(cache.get(&path(0)), Some(0));
+		cache.insert(path(3), 3);
+		cache.insert(path(4), 4);
+		// 1 was left unread for a whole half
+		assert_eq!(cache.get(&path(1)), None);
+		assert_eq!(cache.get(&path(0)), Some(0));
+		assert_eq!(cache.get(&path(4)), Some(4));
 	}
 }
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::Inventory;
+use super::{Score, ScoreMap};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(tag = "function", rename_all = "snake_case")]
+pub enum ValueFunction {
+	// Each unit adds as much
+	#[default]
+	Linear,
+	// ln(1 + quantity), each unit adds less than the one before
+	Logarithmic,
+	// Units past the cap add nothing
+	Capped { cap: usize },
+}
+
+// Value function of each resource, linear for the ones missing
+pub type Values = HashMap<String, ValueFunction>;
+
+impl ValueFunction {
+	pub fn apply(&self, quantity: usize) -> f64 {
+		match self {
+			ValueFunction::Linear => quantity as f64,
+			ValueFunction::Logarithmic => (quantity as f64).ln_1p(),
+			ValueFunction::Capped { cap } => quantity.min(*cap) as f64,
+		}
+	}
+}
+
+// Score of finishing at once, as much as a unit of an optimized resource
+const TIME_VALUE: f64 = 1000.;
+
+// Weight of each resource times the value of its quantity. With the share of the delay the
+// schedule takes, the stock score shrinks by the time weight times that share, so it scales
+// with the stock whatever the simulation. The time left adds its own score, so time counts
+// even without any stock, as with `optimize:(time)` alone
+pub fn inventory_score(score_map: &ScoreMap, values: &Values, time_weight: f32, inventory: Inventory, elapsed: Option<f64>) -> Score {
+	let stock_score: f64 = inventory
+		.into_iter()
+		.map(|(name, quantity)| {
+			let weight = *score_map.get(&name).unwrap_or(&0) as f64;
+			weight * values.get(&name).unwrap_or(&ValueFunction::Linear).apply(quantity)
+		})
+		.sum();
+	let score = match elapsed {
+		Some (elapsed) => {
+			let time_weight = time_weight as f64;
+			stock_score * (1. - time_weight * elapsed).max(0.) + TIME_VALUE * time_weight * (1. - elapsed).max(0.)
+		},
+		None => stock_score,
+	};
+	score.round().clamp(Score::MIN as f64, Score::MAX as f64) as Score
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ValueFunction;
+
+	#[test]
+	fn test_value_functions() {
+		assert_eq!(ValueFunction::Linear.apply(1000), 1000.);
+		assert_eq!(ValueFunction::Logarithmic.apply(0), 0.);
+		assert!(ValueFunction::Logarithmic.apply(2) - ValueFunction::Logarithmic.apply(1) < ValueFunction::Logarithmic.apply(1));
+		assert_eq!(ValueFunction::Capped { cap: 5 }.apply(3), 3.);
+		assert_eq!(ValueFunction::Capped { cap: 5 }.apply(8), 5.);
+		let parsed: ValueFunction = serde_json::from_str("{ \"function\": \"capped\", \"cap\": 5 }").unwrap();
+		assert_eq!(parsed, ValueFunction::Capped { cap: 5 });
+	}
+}
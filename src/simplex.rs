@@ -0,0 +1,153 @@
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+	pub value: f64,
+	pub variables: Vec<f64>,
+	// Unused capacity of each constraint
+	pub slacks: Vec<f64>,
+	// Objective gain per extra unit of each constraint's bound
+	pub duals: Vec<f64>,
+}
+
+struct Tableau {
+	rows: Vec<Vec<f64>>,
+	objective: Vec<f64>,
+	basis: Vec<usize>,
+}
+
+impl Tableau {
+	// Slack variables form the starting basis, so `b` must be non negative
+	fn new(c: &[f64], a: &[Vec<f64>], b: &[f64]) -> Self {
+		let n = c.len();
+		let m = b.len();
+		let rows = a
+			.iter()
+			.zip(b.iter())
+			.enumerate()
+			.map(|(i, (coefficients, bound))| {
+				let mut row = vec![0.; n + m + 1];
+				row[..n].copy_from_slice(coefficients);
+				row[n + i] = 1.;
+				row[n + m] = *bound;
+				row
+			})
+			.collect();
+		let mut objective = vec![0.; n + m + 1];
+		for (j, coefficient) in c.iter().enumerate() {
+			objective[j] = -coefficient;
+		}
+		Self {
+			rows,
+			objective,
+			basis: (n..n + m).collect(),
+		}
+	}
+
+	// Bland's rule: lowest index improving column, avoids cycling on degenerate bases
+	fn entering(&self) -> Option<usize> {
+		let rhs = self.objective.len() - 1;
+		(0..rhs).find(|j| { self.objective[*j] < -EPSILON })
+	}
+
+	fn leaving(&self, column: usize) -> Option<usize> {
+		let rhs = self.objective.len() - 1;
+		self.rows
+			.iter()
+			.enumerate()
+			.filter(|(_, row)| { row[column] > EPSILON })
+			.map(|(i, row)| { (i, row[rhs] / row[column]) })
+			.min_by(|(i, ratio_a), (j, ratio_b)| {
+				ratio_a.partial_cmp(ratio_b)
+					.unwrap_or(std::cmp::Ordering::Equal)
+					.then(self.basis[*i].cmp(&self.basis[*j]))
+			})
+			.map(|(i, _)| { i })
+	}
+
+	fn pivot(&mut self, row: usize, column: usize) {
+		let pivot = self.rows[row][column];
+		for value in self.rows[row].iter_mut() {
+			*value /= pivot;
+		}
+		let pivot_row = self.rows[row].clone();
+		let eliminate = |target: &mut Vec<f64>| {
+			let factor = target[column];
+			if factor.abs() > EPSILON {
+				for (value, pivot_value) in target.iter_mut().zip(pivot_row.iter()) {
+					*value -= factor * pivot_value;
+				}
+			}
+		};
+		for (i, target) in self.rows.iter_mut().enumerate() {
+			if i != row {
+				eliminate(target);
+			}
+		}
+		eliminate(&mut self.objective);
+		self.basis[row] = column;
+	}
+}
+
+// Maximize c.x subject to a.x <= b and x >= 0, with b >= 0
+pub fn maximize(c: &[f64], a: &[Vec<f64>], b: &[f64]) -> Result<Solution, String> {
+	if a.len() != b.len() || a.iter().any(|row| { row.len() != c.len() }) {
+		return Err("Inconsistent linear program dimensions".to_string())
+	}
+	if b.iter().any(|bound| { *bound < 0. }) {
+		return Err("Constraint bounds must be non negative".to_string())
+	}
+	let n = c.len();
+	let m = b.len();
+	let mut tableau = Tableau::new(c, a, b);
+
+	while let Some(column) = tableau.entering() {
+		let row = tableau.leaving(column).ok_or("Unbounded linear program")?;
+		tableau.pivot(row, column);
+	}
+
+	let mut values = vec![0.; n + m];
+	for (row, variable) in tableau.basis.iter().enumerate() {
+		values[*variable] = tableau.rows[row][n + m];
+	}
+	Ok(Solution {
+		value: tableau.objective[n + m],
+		variables: values[..n].to_vec(),
+		slacks: values[n..].to_vec(),
+		duals: tableau.objective[n..n + m].to_vec(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::maximize;
+
+	fn assert_close(a: f64, b: f64) {
+		assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+	}
+
+	#[test]
+	fn test_basic_maximize() {
+		// max 3x + 5y, x <= 4, 2y <= 12, 3x + 2y <= 18
+		let c = vec![3., 5.];
+		let a = vec![vec![1., 0.], vec![0., 2.], vec![3., 2.]];
+		let b = vec![4., 12., 18.];
+		let solution = maximize(&c, &a, &b).unwrap();
+
+		assert_close(solution.value, 36.);
+		assert_close(solution.variables[0], 2.);
+		assert_close(solution.variables[1], 6.);
+		assert_close(solution.slacks[0], 2.);
+		assert_close(solution.duals[1], 1.5);
+		assert_close(solution.duals[2], 1.);
+	}
+
+	#[test]
+	fn test_unbounded_maximize() {
+		let c = vec![1., 1.];
+		let a = vec![vec![1., -1.]];
+		let b = vec![1.];
+
+		assert!(maximize(&c, &a, &b).is_err());
+	}
+}
@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::Rng;
+
 use crate::solver::{Path, Duration, batchify};
 use crate::inventory::{Inventory};
 use crate::ast::{Simulation, Process};
@@ -44,3 +50,246 @@ pub fn simulate(simulation: &Simulation, path: &Path, with_time: bool) -> Result
 		simulate_path(simulation, path)
 	}
 }
+
+// Every process doable with the given inventory, with the inventory it leaves behind.
+// Sorted by name so seeded runs don't depend on the HashMap order
+pub fn get_available_steps(simulation: &Simulation, inventory: &Inventory) -> Vec<(String, Inventory)> {
+	let mut available_steps: Vec<(String, Inventory)> = simulation.processes
+		.iter()
+		.filter_map(|(name, process)| {
+			manage_resources(inventory.clone(), process)
+				.ok()
+				.map(|inventory| { (name.clone(), inventory) })
+		})
+		.collect();
+	available_steps.sort_by(|(a, _), (b, _)| { a.cmp(b) });
+	available_steps
+}
+
+// Random doable path of at most `len` steps, stops early when nothing is doable
+pub fn generate_path<R: Rng>(simulation: &Simulation, len: usize, base_inventory: &Inventory, rng: &mut R) -> Path {
+	let mut path: Path = vec![];
+	let mut simulation_inventory = base_inventory.clone();
+	for _ in 0..len {
+		let available_steps = get_available_steps(simulation, &simulation_inventory);
+		if available_steps.is_empty() {
+			return path
+		}
+		let i = rng.gen_range(0, available_steps.len());
+		let (step_name, updated_inventory) = available_steps[i].clone();
+		path.push(step_name);
+		simulation_inventory = updated_inventory;
+	};
+	path
+}
+
+// Times the process can run in a row from the inventory, None when nothing runs out
+pub fn max_repeats(process: &Process, inventory: &Inventory) -> Option<usize> {
+	let mut max: Option<usize> = None;
+	for input in &process.input {
+		let needed: usize = process.input.iter().filter(|other| { other.name == input.name }).map(|other| { other.quantity }).sum();
+		let produced: usize = process.output.iter().filter(|output| { output.name == input.name }).map(|output| { output.quantity }).sum();
+		let available = inventory.get(&input.name).copied().unwrap_or(0);
+		if available < needed {
+			return Some(0)
+		}
+		if produced < needed {
+			let repeats = (available - needed) / (needed - produced) + 1;
+			max = Some(max.map_or(repeats, |max| { max.min(repeats) }));
+		}
+	}
+	max
+}
+
+// Same inventory as running the process `count` times in a row, which has to be doable
+// Outputs are added first, resources given back by the process never run below zero
+pub fn repeat_process(mut inventory: Inventory, process: &Process, count: usize) -> Inventory {
+	for output in &process.output {
+		*inventory.entry(output.name.clone()).or_insert(0) += output.quantity * count;
+	}
+	for input in &process.input {
+		*inventory.entry(input.name.clone()).or_insert(0) -= input.quantity * count;
+	}
+	inventory
+}
+
+struct PrefixNode {
+	inventory: Inventory,
+	children: HashMap<String, usize>,
+}
+
+struct Trie {
+	nodes: Vec<PrefixNode>,
+}
+
+impl Trie {
+	fn new(inventory: Inventory) -> Self {
+		Self { nodes: vec![PrefixNode { inventory, children: HashMap::new() }] }
+	}
+
+	// Nodes of the longest prefix of the path in the trie, the root excluded
+	fn walk(&self, path: &Path) -> Vec<usize> {
+		let mut nodes = vec![];
+		let mut node = 0;
+		while let Some (child) = path.get(nodes.len()).and_then(|step| { self.nodes[node].children.get(step) }) {
+			node = *child;
+			nodes.push(node);
+		}
+		nodes
+	}
+}
+
+struct Tries {
+	// Trie new prefixes are added to
+	recent: Trie,
+	// Recent trie from before it filled up, prefixes read from it are added back to the recent one
+	old: Trie,
+	// Bumped when the tries rotate, node indexes from before are stale
+	epoch: usize,
+}
+
+// Inventories after each prefix of the simulated paths, so paths sharing a prefix
+// with an earlier one only simulate the steps that follow it
+pub struct PrefixCache {
+	tries: Mutex<Tries>,
+	// Nodes kept over both tries, only prefixes left unread for a whole trie are dropped
+	capacity: usize,
+	reused_steps: AtomicUsize,
+	simulated_steps: AtomicUsize,
+}
+
+impl PrefixCache {
+	pub fn new(simulation: &Simulation, capacity: usize) -> Self {
+		let tries = Tries {
+			recent: Trie::new(simulation.inventory.clone()),
+			old: Trie::new(simulation.inventory.clone()),
+			epoch: 0,
+		};
+		Self {
+			tries: Mutex::new(tries),
+			capacity,
+			reused_steps: AtomicUsize::new(0),
+			simulated_steps: AtomicUsize::new(0),
+		}
+	}
+
+	// Steps read from the tries, and steps simulated
+	pub fn counts(&self) -> (usize, usize) {
+		(self.reused_steps.load(Ordering::Relaxed), self.simulated_steps.load(Ordering::Relaxed))
+	}
+
+	// Same inventory as simulating the path step by step from the initial one
+	pub fn inventory(&self, simulation: &Simulation, path: &Path) -> Result<Inventory, String> {
+		let poisoned = |_| { "Poisoned prefix cache".to_string() };
+		// Inventories of the steps the old trie holds past the recent one
+		let (mut node, mut depth, epoch, mut inventories, mut inventory) = {
+			let tries = self.tries.lock().map_err(poisoned)?;
+			let recent = tries.recent.walk(path);
+			let node = recent.last().cloned().unwrap_or(0);
+			let promoted: Vec<Inventory> = tries.old
+				.walk(path)
+				.iter()
+				.skip(recent.len())
+				.map(|node| { tries.old.nodes[*node].inventory.clone() })
+				.collect();
+			let inventory = promoted.last().unwrap_or(&tries.recent.nodes[node].inventory).clone();
+			(node, recent.len(), tries.epoch, promoted, inventory)
+		};
+		self.reused_steps.fetch_add(depth + inventories.len(), Ordering::Relaxed);
+
+		// Simulated out of the lock, other threads keep reading the tries meanwhile
+		let simulated = path.len() - depth - inventories.len();
+		for step in &path[depth + inventories.len()..] {
+			let process = simulation.processes.get(step).ok_or(format!("Unknown process: {:?}", step))?;
+			inventory = manage_resources(inventory, process)?;
+			inventories.push(inventory.clone());
+		}
+		self.simulated_steps.fetch_add(simulated, Ordering::Relaxed);
+
+		let mut tries = self.tries.lock().map_err(poisoned)?;
+		if tries.epoch != epoch {
+			return Ok(inventory)
+		}
+		if tries.recent.nodes.len() + inventories.len() > self.capacity / 2 {
+			// The path is added again to the new recent trie, when it fits
+			let kept: Vec<Inventory> = tries.recent
+				.walk(path)
+				.iter()
+				.take(depth)
+				.map(|node| { tries.recent.nodes[*node].inventory.clone() })
+				.collect();
+			let root = tries.recent.nodes[0].inventory.clone();
+			tries.old = std::mem::replace(&mut tries.recent, Trie::new(root));
+			tries.epoch += 1;
+			if path.len() >= self.capacity / 2 {
+				return Ok(inventory)
+			}
+			inventories = kept.into_iter().chain(inventories).collect();
+			node = 0;
+			depth = 0;
+		}
+		for (step, step_inventory) in path[depth..].iter().zip(inventories) {
+			// Another thread may have added the same steps since
+			node = match tries.recent.nodes[node].children.get(step) {
+				Some (child) => *child,
+				None => {
+					let child = tries.recent.nodes.len();
+					tries.recent.nodes.push(PrefixNode { inventory: step_inventory, children: HashMap::new() });
+					tries.recent.nodes[node].children.insert(step.clone(), child);
+					child
+				},
+			};
+		}
+		Ok(inventory)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use crate::ast::parse;
+	use crate::solver::Path;
+	use super::{PrefixCache, generate_path, simulate};
+
+	#[test]
+	fn test_prefix_cache_matches_simulate() {
+		let simulation = parse(read_to_string("ressources/pomme").unwrap()).unwrap();
+		let cache = PrefixCache::new(&simulation, 50);
+		let mut rng = StdRng::seed_from_u64(0);
+
+		for _ in 0..20 {
+			let path = generate_path(&simulation, 30, &simulation.inventory, &mut rng);
+			for len in &[path.len(), path.len() / 2] {
+				let prefix = path[..*len].to_vec();
+				let (expected, _) = simulate(&simulation, &prefix, false).unwrap();
+				assert_eq!(cache.inventory(&simulation, &prefix).unwrap(), expected);
+			}
+		}
+		let (reused, _) = cache.counts();
+		assert!(reused > 0);
+	}
+
+	#[test]
+	fn test_prefix_cache_keeps_the_paths_read_lately() {
+		let simulation = parse(read_to_string("ressources/pomme").unwrap()).unwrap();
+		let mut rng = StdRng::seed_from_u64(0);
+		let paths: Vec<Path> = (0..3).map(|_| { generate_path(&simulation, 30, &simulation.inventory, &mut rng) }).collect();
+		// A trie holds any one of the paths, not always two
+		let longest = paths.iter().map(|path| { path.len() }).max().unwrap();
+		let cache = PrefixCache::new(&simulation, 2 * (longest + 1));
+		let reused = |path: &Path| {
+			let (before, _) = cache.counts();
+			assert_eq!(cache.inventory(&simulation, path).unwrap(), simulate(&simulation, path, false).unwrap().0);
+			cache.counts().0 - before
+		};
+
+		reused(&paths[0]);
+		reused(&paths[1]);
+		assert_eq!(reused(&paths[0]), paths[0].len());
+		reused(&paths[2]);
+		assert_eq!(reused(&paths[0]), paths[0].len());
+	}
+}
@@ -1,16 +1,53 @@
-use std::fs::File;
-use std::io::Read;
+use std::str::FromStr;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
 use crate::{
-	genetic::solve as genetic_solve,
-	ast::{Simulation, Process},
-	genetic_config_parser::parse_genetic_config,
+	genetic::{solve as genetic_solve, solve_with_stats as genetic_solve_with_stats},
+	mcts::solve as mcts_solve,
+	local_search::{annealing::solve as annealing_solve, tabu::solve as tabu_solve},
+	planner::solve as planner_solve,
+	beam::{solve as beam_solve, Config as BeamConfig},
+	nsga2::{solve as nsga2_solve, solve_with_front as nsga2_solve_with_front},
+	portfolio::{run as portfolio_run, Summary},
+	ast::{Simulation, Process, Resource},
+	inventory::Inventory,
+	control::Control,
+	score::CacheStats,
+	genetic_config_parser::{parse_genetic_config, parse_config_section},
 	utils::generalize_error,
 	check::{consume_resources, manage_multi_resources}
 };
 
 pub enum Algorithm {
-	Genetic
+	Genetic,
+	Mcts,
+	Annealing,
+	Tabu,
+	Planner,
+	Greedy,
+	Beam,
+	Nsga2,
+	Portfolio,
+}
+
+impl FromStr for Algorithm {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		match name {
+			"genetic" => Ok(Algorithm::Genetic),
+			"mcts" => Ok(Algorithm::Mcts),
+			"annealing" => Ok(Algorithm::Annealing),
+			"tabu" => Ok(Algorithm::Tabu),
+			"planner" => Ok(Algorithm::Planner),
+			"greedy" => Ok(Algorithm::Greedy),
+			"beam" => Ok(Algorithm::Beam),
+			"nsga2" => Ok(Algorithm::Nsga2),
+			"portfolio" => Ok(Algorithm::Portfolio),
+			_ => Err(format!("Unknown algorithm: {}", name)),
+		}
+	}
 }
 
 pub type Duration = usize;
@@ -19,25 +56,90 @@ pub type Path = Vec<Step>;
 pub type Batch = (Duration, Path);
 pub type Production = Vec<Batch>;
 
-// TODO: unmock
-fn get_algorithm(_simulation: &Simulation) -> Algorithm {
-	Algorithm::Genetic
-}
+// Returns the seed used, to replay the run, the score cache stats of genetic runs and the members'
+// summary of portfolio runs. The seed comes from the argument, then the config's "seed", and is
+// random otherwise
+pub fn solve(simulation: Simulation, algorithm: Algorithm, config_content: &str, control: &Control, seed: Option<u64>) -> Result<(Production, u64, Option<CacheStats>, Option<Summary>), String> {
+	let seed = match seed {
+		Some (seed) => seed,
+		None => parse_config_section::<Option<u64>>(config_content, "seed")?
+			.unwrap_or_else(|| { rand::thread_rng().gen() }),
+	};
+	let mut rng = StdRng::seed_from_u64(seed);
 
-pub fn solve(simulation: Simulation) -> Result<Production, String> {
-	let algorithm = get_algorithm(&simulation);
+	let mut cache = None;
+	let mut summary = None;
+	let production = match algorithm {
+		Algorithm::Genetic => {
+			let genetic_config = parse_genetic_config(config_content.to_string())?;
+			genetic_solve(simulation, genetic_config, control, &mut rng).map_err(generalize_error).map(|(production, stats)| {
+				cache = Some(stats.cache);
+				production
+			})
+		}
+		Algorithm::Nsga2 => {
+			let nsga2_config = parse_config_section(config_content, "nsga2")?;
+			nsga2_solve(simulation, nsga2_config, control, &mut rng)
+		}
+		Algorithm::Portfolio => {
+			let portfolio_config = parse_config_section(config_content, "portfolio")?;
+			portfolio_run(simulation, portfolio_config, config_content, control, &mut rng).map(|(production, members)| {
+				summary = Some(members);
+				production
+			})
+		}
+		algorithm => solve_with_config(simulation, algorithm, config_content, control, &mut rng),
+	}?;
+	Ok((production, seed, cache, summary))
+}
 
+// Same as solve without side effects such as plots, so it can run in any thread
+pub fn solve_with_config(simulation: Simulation, algorithm: Algorithm, config_content: &str, control: &Control, rng: &mut StdRng) -> Result<Production, String> {
 	match algorithm {
 		Algorithm::Genetic => {
-			let mut genetic_config_file = File::open("generic_config.json").map_err(generalize_error)?;
-			let mut genetic_config_content = String::new();
-			genetic_config_file.read_to_string(&mut genetic_config_content).unwrap();
-			let genetic_config = parse_genetic_config(genetic_config_content)?;
-			genetic_solve(simulation, genetic_config).map_err(generalize_error)
+			let genetic_config = parse_genetic_config(config_content.to_string())?;
+			genetic_solve_with_stats(simulation, genetic_config, control, rng).map(|(production, _)| { production })
+		}
+		Algorithm::Mcts => {
+			let mcts_config = parse_config_section(config_content, "mcts")?;
+			mcts_solve(simulation, mcts_config, control, rng)
+		}
+		Algorithm::Annealing => {
+			let annealing_config = parse_config_section(config_content, "annealing")?;
+			annealing_solve(simulation, annealing_config, control, rng)
+		}
+		Algorithm::Tabu => {
+			let tabu_config = parse_config_section(config_content, "tabu")?;
+			tabu_solve(simulation, tabu_config, control, rng)
+		}
+		Algorithm::Planner => {
+			let planner_config = parse_config_section(config_content, "planner")?;
+			planner_solve(simulation, planner_config, control)
+		}
+		Algorithm::Greedy => {
+			let beam_config: BeamConfig = parse_config_section(config_content, "beam")?;
+			beam_solve(simulation, beam_config.greedy(), control)
+		}
+		Algorithm::Beam => {
+			let beam_config = parse_config_section(config_content, "beam")?;
+			beam_solve(simulation, beam_config, control)
+		}
+		Algorithm::Nsga2 => {
+			let nsga2_config = parse_config_section(config_content, "nsga2")?;
+			nsga2_solve_with_front(simulation, nsga2_config, control, rng).map(|(production, _)| { production })
+		}
+		Algorithm::Portfolio => {
+			let portfolio_config = parse_config_section(config_content, "portfolio")?;
+			portfolio_run(simulation, portfolio_config, config_content, control, rng).map(|(production, _)| { production })
 		}
 	}
 }
 
+// Steps of the production in order
+pub fn flatten(production: &Production) -> Path {
+	production.iter().flat_map(|(_, path)| { path.clone() }).collect()
+}
+
 fn create_batch(processes: Vec<&Process>) -> Result<Batch, String> {
 	let duration = processes
 		.iter()
@@ -68,9 +170,9 @@ pub fn batchify(simulation: &Simulation, process_names: Path) -> Result<Producti
 				Ok(batched)
 			},
 			None => {
-				let tmp = inventory.clone();
-				inventory = manage_multi_resources(base_inventory.clone(), batch_processes.clone())?;
-				base_inventory = tmp;
+				// The new batch starts once every process of the current one is done
+				base_inventory = manage_multi_resources(base_inventory.clone(), batch_processes.clone())?;
+				inventory = consume_resources(&process.input, base_inventory.clone())?;
 				let batch = create_batch(batch_processes.clone())?;
 				batched.push(batch);
 				batch_processes = vec![&process];
@@ -85,3 +187,55 @@ pub fn batchify(simulation: &Simulation, process_names: Path) -> Result<Producti
 	};
 	Ok(batched)
 }
+
+// Takes the inputs of a process from the inventory in place, or leaves it as it was
+fn take(inventory: &mut Inventory, input: &[Resource]) -> bool {
+	for (i, resource) in input.iter().enumerate() {
+		match inventory.get_mut(&resource.name) {
+			Some (quantity) if *quantity >= resource.quantity => *quantity -= resource.quantity,
+			_ => {
+				give(inventory, &input[..i]);
+				return false
+			},
+		}
+	}
+	true
+}
+
+fn give(inventory: &mut Inventory, output: &[Resource]) {
+	for resource in output {
+		match inventory.get_mut(&resource.name) {
+			Some (quantity) => *quantity += resource.quantity,
+			None => { inventory.insert(resource.name.clone(), resource.quantity); },
+		}
+	}
+}
+
+// Total duration of the batches batchify makes of the path, without making them
+pub fn makespan(simulation: &Simulation, path: &[Step]) -> Result<Duration, String> {
+	let mut inventory = simulation.inventory.clone();
+	let mut base_inventory = simulation.inventory.clone();
+	let mut batch: Vec<&Process> = vec![];
+	let mut makespan = 0;
+	for step in path {
+		let process = simulation.processes.get(step).ok_or(format!("Unable to find process: {}", step))?;
+		if take(&mut inventory, &process.input) {
+			batch.push(process);
+			continue
+		}
+		// The new batch starts once every process of the current one is done
+		makespan += batch.iter().map(|process| { process.duration }).max().unwrap_or(0);
+		for done in batch.drain(..) {
+			if !take(&mut base_inventory, &done.input) {
+				return Err("Not enough available resources".to_string())
+			}
+			give(&mut base_inventory, &done.output);
+		}
+		inventory.clone_from(&base_inventory);
+		if !take(&mut inventory, &process.input) {
+			return Err("Not enough available resources".to_string())
+		}
+		batch.push(process);
+	}
+	Ok(makespan + batch.iter().map(|process| { process.duration }).max().unwrap_or(0))
+}
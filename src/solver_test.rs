@@ -1,15 +1,16 @@
-use std::fs::File;
-use std::io::Read;
 
-use crate::utils::generalize_error;
-use crate::ast::{Simulation, parse};
-use crate::solver::{batchify, Batch};
+use rand::{SeedableRng, rngs::StdRng};
 
-fn test_provider(simulation_file_path: String) -> Result<Simulation, String> {
-	let mut simulation_file = File::open(simulation_file_path).map_err(generalize_error)?;
-	let mut simulation_content = String::new();
-	simulation_file.read_to_string(&mut simulation_content).unwrap();
-	parse(simulation_content)
+use crate::test_utils::test_provider;
+use crate::check::{check, Output};
+use crate::control::Control;
+use crate::score::{Scorer, BroScore};
+use crate::solver::{batchify, flatten, makespan, solve_with_config, Algorithm, Batch};
+
+// File in the temp dir no other test, nor another run of the tests, writes to
+fn temp_path(test: &str, name: &str) -> String {
+	let file = format!("krpsim_{}_{}_{}", std::process::id(), test, name);
+	std::env::temp_dir().join(file).to_string_lossy().to_string()
 }
 
 #[test]
@@ -44,3 +45,111 @@ fn batchify_happy_path() {
 		Err (err) => { println!("An error occured: {}", err) }
 	}
 }
+
+#[test]
+fn batchify_sequential_batches() {
+	let processes: Vec<String> = vec![
+		"achat_materiel".to_string(),
+		"realisation_produit".to_string(),
+		"livraison".to_string(),
+	];
+	let expected: Vec<Batch> = vec![
+		(10, vec!["achat_materiel".to_string()]),
+		(30, vec!["realisation_produit".to_string()]),
+		(20, vec!["livraison".to_string()]),
+	];
+	let simulation = test_provider("ressources/simple".to_string()).unwrap();
+	assert_eq!(batchify(&simulation, processes), Ok(expected));
+}
+
+#[test]
+fn makespan_sums_the_batches() {
+	for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+		let simulation = test_provider(format!("ressources/{}", name)).unwrap();
+		for algorithm in [Algorithm::Greedy, Algorithm::Beam] {
+			let production = solve_with_config(simulation.clone(), algorithm, "{}", &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+			let path = flatten(&production);
+			let expected: usize = batchify(&simulation, path.clone()).unwrap().iter().map(|(duration, _)| { duration }).sum();
+			assert_eq!(makespan(&simulation, &path), Ok(expected), "{}", name);
+		}
+	}
+	let simulation = test_provider("ressources/simple".to_string()).unwrap();
+	assert!(makespan(&simulation, &["livraison".to_string()]).is_err());
+}
+
+#[test]
+fn same_seed_same_production() {
+	let simulation = test_provider("ressources/pomme".to_string()).unwrap();
+	let config_content = r#"{
+		"mutation_chance": 0.1, "max_depth": 30, "generation_size": 20, "iterations": 5, "time_weight": 0.1,
+		"mcts": { "iterations": 200 }, "annealing": { "iterations": 500 }, "tabu": { "iterations": 50 },
+		"nsga2": { "generation_size": 20, "iterations": 5, "max_depth": 30 }
+	}"#;
+	for name in &["genetic", "mcts", "annealing", "tabu", "nsga2"] {
+		let run = || {
+			let algorithm: Algorithm = name.parse().unwrap();
+			let mut rng = StdRng::seed_from_u64(42);
+			solve_with_config(simulation.clone(), algorithm, config_content, &Control::default(), &mut rng).unwrap()
+		};
+		assert_eq!(run(), run());
+	}
+}
+
+#[test]
+fn resumed_genetic_run_matches_uninterrupted_one() {
+	let simulation = test_provider("ressources/pomme".to_string()).unwrap();
+	let full = temp_path("resumed_genetic_run", "full_checkpoint.json");
+	let half = temp_path("resumed_genetic_run", "half_checkpoint.json");
+	let config = |iterations: usize, checkpoint: &str, resume: Option<&str>| {
+		serde_json::json!({
+			"mutation_chance": 0.3, "max_depth": 30, "generation_size": 20, "iterations": iterations,
+			"checkpoint": checkpoint, "checkpoint_interval": 3, "resume": resume,
+		}).to_string()
+	};
+	let run = |config_content: String| {
+		let mut rng = StdRng::seed_from_u64(7);
+		solve_with_config(simulation.clone(), Algorithm::Genetic, &config_content, &Control::default(), &mut rng).unwrap()
+	};
+
+	let expected = run(config(8, &full, None));
+	run(config(4, &half, None));
+	assert_eq!(run(config(8, &half, Some(&half))), expected);
+	// Evaluations per second depend on the machine, not on the seed
+	let islands = |path: &str| {
+		let mut checkpoint: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+		for island in checkpoint["islands"].as_array_mut().unwrap() {
+			island["stats"]["evaluations_per_second"] = serde_json::Value::Null;
+		}
+		checkpoint["islands"].take()
+	};
+	assert_eq!(islands(&full), islands(&half));
+	std::fs::remove_file(full).ok();
+	std::fs::remove_file(half).ok();
+}
+
+#[test]
+fn warm_started_genetic_run_keeps_its_seed() {
+	let simulation = test_provider("ressources/pomme".to_string()).unwrap();
+	let scorer = Scorer::new(simulation.clone(), 0.1, BroScore::Leo);
+	let greedy = solve_with_config(simulation.clone(), Algorithm::Greedy, "{}", &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+	let file = temp_path("warm_started_genetic_run", "warm_start.json");
+	std::fs::write(&file, serde_json::to_string(&greedy).unwrap()).unwrap();
+	let config = serde_json::json!({
+		"mutation_chance": 0.3, "max_depth": 100, "generation_size": 20, "iterations": 1,
+		"warm_start": { "files": [file], "random_fraction": 0.9 }
+	}).to_string();
+	let production = solve_with_config(simulation, Algorithm::Genetic, &config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+	std::fs::remove_file(&file).unwrap();
+
+	assert!(scorer.score(&flatten(&production)).unwrap() >= scorer.score(&flatten(&greedy)).unwrap());
+}
+
+#[test]
+fn hugo_scorer_solves_every_resource_file() {
+	let config = serde_json::json!({ "max_depth": 30, "generation_size": 20, "iterations": 3, "scorer": "hugo" }).to_string();
+	for name in &["ikea", "inception", "pomme", "recre", "simple", "steak"] {
+		let simulation = test_provider(format!("ressources/{}", name)).unwrap();
+		let production = solve_with_config(simulation.clone(), Algorithm::Genetic, &config, &Control::default(), &mut StdRng::seed_from_u64(0)).unwrap();
+		assert!(check(simulation, Output { steps: flatten(&production) }).is_ok(), "{}", name);
+	}
+}
@@ -0,0 +1,12 @@
+use std::fs::File;
+use std::io::Read;
+
+use crate::utils::generalize_error;
+use crate::ast::{Simulation, parse};
+
+pub fn test_provider(simulation_file_path: String) -> Result<Simulation, String> {
+	let mut simulation_file = File::open(simulation_file_path).map_err(generalize_error)?;
+	let mut simulation_content = String::new();
+	simulation_file.read_to_string(&mut simulation_content).unwrap();
+	parse(simulation_content)
+}
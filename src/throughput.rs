@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{
+	ast::{Simulation, Process, Resource},
+	simplex::maximize,
+};
+
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+	// Consumption can't exceed production
+	Balance,
+	// Held resources (machines) are busy for the whole process duration
+	Capacity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+	// Optimized resources produced per time unit
+	pub rate: f64,
+	// Executions per time unit of each process
+	pub process_rates: Vec<(String, f64)>,
+	// Saturated constraints with their shadow price
+	pub binding: Vec<(String, Bound, f64)>,
+}
+
+fn quantity(resources: &[Resource], name: &str) -> f64 {
+	resources
+		.iter()
+		.filter(|resource| { resource.name == name })
+		.map(|resource| { resource.quantity as f64 })
+		.sum()
+}
+
+// Net production of a resource per execution
+fn net(process: &Process, name: &str) -> f64 {
+	quantity(&process.output, name) - quantity(&process.input, name)
+}
+
+// Quantity taken at start and given back at the end of an execution
+fn held(process: &Process, name: &str) -> f64 {
+	quantity(&process.input, name).min(quantity(&process.output, name))
+}
+
+// Maximum sustainable production rate of the optimized resources, as a linear program
+// over the execution rate of each process
+pub fn analyse(simulation: &Simulation) -> Result<Throughput, String> {
+	if simulation.optimize.is_empty() {
+		return Err("No resource to optimize".to_string())
+	}
+	let mut processes: Vec<&Process> = simulation.processes.values().collect();
+	processes.sort_by(|a, b| { a.name.cmp(&b.name) });
+	let resources: BTreeSet<&String> = simulation.inventory.keys()
+		.chain(processes.iter().flat_map(|process| {
+			process.input.iter().chain(process.output.iter()).map(|resource| { &resource.name })
+		}))
+		.collect();
+
+	let mut constraints: Vec<(String, Bound)> = vec![];
+	let mut a: Vec<Vec<f64>> = vec![];
+	let mut b: Vec<f64> = vec![];
+	for name in resources {
+		let balance: Vec<f64> = processes.iter().map(|process| { -net(process, name) }).collect();
+		if balance.iter().any(|coefficient| { *coefficient > EPSILON }) {
+			constraints.push((name.clone(), Bound::Balance));
+			a.push(balance);
+			b.push(0.);
+		}
+		let capacity: Vec<f64> = processes
+			.iter()
+			.map(|process| { held(process, name) * process.duration as f64 })
+			.collect();
+		if capacity.iter().any(|coefficient| { *coefficient > EPSILON }) {
+			constraints.push((name.clone(), Bound::Capacity));
+			a.push(capacity);
+			b.push(*simulation.inventory.get(name).unwrap_or(&0) as f64);
+		}
+	}
+	let c: Vec<f64> = processes
+		.iter()
+		.map(|process| {
+			simulation.optimize.iter().map(|name| { net(process, name) }).sum()
+		})
+		.collect();
+
+	let solution = maximize(&c, &a, &b)
+		.map_err(|err| { format!("Throughput analysis failed: {}", err) })?;
+	let process_rates = processes
+		.iter()
+		.zip(solution.variables.iter())
+		.map(|(process, rate)| { (process.name.clone(), *rate) })
+		.collect();
+	let binding = constraints
+		.into_iter()
+		.zip(solution.slacks.iter().zip(solution.duals.iter()))
+		.filter(|(_, (slack, dual))| { slack.abs() < EPSILON && **dual > EPSILON })
+		.map(|((name, bound), (_, dual))| { (name, bound, *dual) })
+		.collect();
+	Ok(Throughput {
+		rate: solution.value,
+		process_rates,
+		binding,
+	})
+}
+
+// Long chains (seconds to years) give tiny rates, keep them readable
+fn format_rate(rate: f64) -> String {
+	if rate != 0. && rate.abs() < 1e-3 {
+		format!("{:.4e}", rate)
+	} else {
+		format!("{:.4}", rate)
+	}
+}
+
+impl fmt::Display for Throughput {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Maximum sustainable rate: {} per time unit", format_rate(self.rate))?;
+		writeln!(f, "Process rates:")?;
+		for (name, rate) in self.process_rates.iter().filter(|(_, rate)| { *rate > EPSILON }) {
+			writeln!(f, "  {}: {}", name, format_rate(*rate))?;
+		}
+		writeln!(f, "Binding resources:")?;
+		for (name, bound, price) in &self.binding {
+			let kind = match bound {
+				Bound::Balance => "balance",
+				Bound::Capacity => "capacity",
+			};
+			writeln!(f, "  {} ({}): {} per unit", name, kind, format_rate(*price))?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use crate::ast::parse;
+	use super::{analyse, Bound};
+
+	#[test]
+	fn test_factorio_throughput() {
+		let simulation = parse(read_to_string("factorio.sim").unwrap()).unwrap();
+		let throughput = analyse(&simulation).unwrap();
+
+		assert!((throughput.rate - 0.2).abs() < 1e-6);
+		let iron_rate = throughput.process_rates
+			.iter()
+			.find(|(name, _)| { name == "assemble_iron" })
+			.map(|(_, rate)| { *rate })
+			.unwrap();
+		assert!((iron_rate - 0.6).abs() < 1e-6);
+		assert!(throughput.binding
+			.iter()
+			.any(|(name, bound, _)| { name == "assembly_machine" && *bound == Bound::Capacity }));
+	}
+}
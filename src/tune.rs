@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde_json::Value;
+
+use crate::{
+	ast::Simulation,
+	control::Control,
+	genetic_config_parser::{apply_overrides, parse_config_section, parse_genetic_config},
+	score::{Score, Scorer, BroScore, Values},
+	solver::{Algorithm, flatten, solve_with_config},
+};
+
+// Candidate values of each genetic config key, dotted keys reach nested values
+pub type Space = BTreeMap<String, Vec<Value>>;
+
+pub fn default_space() -> Space {
+	let mut space = Space::new();
+	space.insert("mutation_chance".to_string(), vec![0.01, 0.05, 0.1, 0.2, 0.4].into_iter().map(Value::from).collect());
+	space.insert("generation_size".to_string(), vec![20, 50, 100, 200].into_iter().map(Value::from).collect());
+	space.insert("max_depth".to_string(), vec![50, 100, 200].into_iter().map(Value::from).collect());
+	space.insert("iterations".to_string(), vec![10, 20, 40].into_iter().map(Value::from).collect());
+	space.insert("time_weight".to_string(), vec![0., 0.1, 0.5].into_iter().map(Value::from).collect());
+	space
+}
+
+pub enum Search {
+	// Number of candidates drawn from the space
+	Random(usize),
+	// Every combination of the space
+	Grid,
+}
+
+pub struct Options {
+	pub search: Search,
+	// Evaluate every candidate with a fraction of its iterations, keep the best half, and so on
+	pub halving: bool,
+	// Runs of each candidate on each simulation, seeded 0, 1, ...
+	pub seeds: u64,
+	// Seeds the random search
+	pub seed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trial {
+	pub params: BTreeMap<String, Value>,
+	// Iterations given to each run in the last round the trial took part in
+	pub iterations: usize,
+	// Average score on each simulation, or why the candidate failed
+	pub scores: Result<Vec<f64>, String>,
+	// Average rank over the simulations in the last round, lower is better
+	pub rank: f64,
+	pub round: usize,
+}
+
+pub struct Results {
+	pub files: Vec<String>,
+	// Best first
+	pub trials: Vec<Trial>,
+}
+
+fn candidates(space: &Space, search: &Search, rng: &mut StdRng) -> Vec<BTreeMap<String, Value>> {
+	match search {
+		Search::Random(count) => {
+			(0..*count)
+				.map(|_| {
+					space
+						.iter()
+						.filter(|(_, values)| { !values.is_empty() })
+						.map(|(key, values)| { (key.clone(), values[rng.gen_range(0, values.len())].clone()) })
+						.collect()
+				})
+				.collect()
+		},
+		Search::Grid => {
+			space.iter().fold(vec![BTreeMap::new()], |combinations, (key, values)| {
+				combinations
+					.iter()
+					.flat_map(|combination| {
+						values.iter().map(move |value| {
+							let mut combination = combination.clone();
+							combination.insert(key.clone(), value.clone());
+							combination
+						})
+					})
+					.collect()
+			})
+		},
+	}
+}
+
+fn candidate_config(base_config: &str, params: &BTreeMap<String, Value>) -> Result<String, String> {
+	let overrides: Vec<String> = params
+		.iter()
+		.map(|(key, value)| { format!("{}={}", key, value) })
+		.collect();
+	apply_overrides(base_config, &overrides)
+}
+
+// Full iterations of the candidate
+fn iterations(config_content: &str) -> Result<usize, String> {
+	parse_genetic_config(config_content.to_string())?;
+	Ok(parse_config_section::<Option<usize>>(config_content, "iterations")?.unwrap_or(10))
+}
+
+struct Evaluator<'a> {
+	simulations: &'a [Simulation],
+	// Same scorer for every candidate, whatever their time_weight
+	scorers: Vec<Scorer>,
+	base_config: &'a str,
+	seeds: u64,
+	control: &'a Control,
+}
+
+impl<'a> Evaluator<'a> {
+	fn scores(&self, params: &BTreeMap<String, Value>, fraction: f64) -> Result<(usize, Vec<f64>), String> {
+		let config_content = candidate_config(self.base_config, params)?;
+		let full_iterations = iterations(&config_content)?;
+		let iterations = ((full_iterations as f64 * fraction).ceil() as usize).max(1);
+		let config_content = apply_overrides(&config_content, &[format!("iterations={}", iterations)])?;
+		let scores = self.simulations
+			.iter()
+			.zip(&self.scorers)
+			.map(|(simulation, scorer)| {
+				let total = (0..self.seeds)
+					.map(|seed| {
+						let mut rng = StdRng::seed_from_u64(seed);
+						let production = solve_with_config(simulation.clone(), Algorithm::Genetic, &config_content, self.control, &mut rng)?;
+						scorer.score(&flatten(&production))
+					})
+					.sum::<Result<Score, String>>()?;
+				Ok(total as f64 / self.seeds.max(1) as f64)
+			})
+			.collect::<Result<Vec<f64>, String>>()?;
+		Ok((iterations, scores))
+	}
+}
+
+// Rank of each trial on each simulation, ties share their average rank
+fn rank(trials: &mut [Trial], simulations: usize) {
+	for trial in trials.iter_mut() {
+		trial.rank = 0.;
+	}
+	for simulation in 0..simulations {
+		let scores: Vec<Option<f64>> = trials
+			.iter()
+			.map(|trial| { trial.scores.as_ref().ok().map(|scores| { scores[simulation] }) })
+			.collect();
+		for (i, score) in scores.iter().enumerate() {
+			let rank = match score {
+				Some (score) => {
+					let better = scores.iter().filter(|other| { other.is_some_and(|other| { other > *score }) }).count();
+					let equal = scores.iter().filter(|other| { **other == Some(*score) }).count();
+					better as f64 + (equal + 1) as f64 / 2.
+				},
+				None => trials.len() as f64,
+			};
+			trials[i].rank += rank / simulations as f64;
+		}
+	}
+	trials.sort_by(|a, b| { a.rank.partial_cmp(&b.rank).unwrap_or(std::cmp::Ordering::Equal) });
+}
+
+pub fn tune(files: Vec<String>, simulations: &[Simulation], base_config: &str, space: &Space, options: &Options, control: &Control) -> Result<Results, String> {
+	parse_genetic_config(base_config.to_string())?;
+	let base_time_weight = parse_config_section::<Option<f32>>(base_config, "time_weight")?.unwrap_or(0.1);
+	let bro_score = parse_config_section::<Option<BroScore>>(base_config, "scorer")?.unwrap_or_default();
+	let values = parse_config_section::<Option<Values>>(base_config, "values")?.unwrap_or_default();
+	// The deepest candidate's horizon, so no candidate's makespan runs past it
+	let max_depth = space
+		.get("max_depth")
+		.into_iter()
+		.flatten()
+		.filter_map(Value::as_u64)
+		.map(|max_depth| { max_depth as usize })
+		.chain(parse_config_section::<Option<usize>>(base_config, "max_depth")?)
+		.max()
+		.unwrap_or(100);
+	let evaluator = Evaluator {
+		simulations,
+		scorers: simulations
+			.iter()
+			.map(|simulation| { Scorer::new(simulation.clone(), base_time_weight, bro_score).with_values(values.clone()).with_horizon(max_depth) })
+			.collect(),
+		base_config,
+		seeds: options.seeds,
+		control,
+	};
+	let mut rng = StdRng::seed_from_u64(options.seed);
+	let mut remaining: Vec<Trial> = candidates(space, &options.search, &mut rng)
+		.into_iter()
+		.map(|params| { Trial { params, iterations: 0, scores: Err("Not evaluated".to_string()), rank: 0., round: 0 } })
+		.collect();
+	if remaining.is_empty() {
+		return Err("The search space has no candidate".to_string())
+	}
+	let rounds = if options.halving { (remaining.len() as f64).log2().ceil() as usize + 1 } else { 1 };
+	let mut eliminated: Vec<Trial> = vec![];
+	for round in 0..rounds {
+		if control.is_cancelled() {
+			break
+		}
+		let fraction = 0.5f64.powi((rounds - round - 1) as i32);
+		for trial in remaining.iter_mut() {
+			let result = evaluator.scores(&trial.params, fraction);
+			trial.round = round;
+			trial.iterations = result.as_ref().map(|(iterations, _)| { *iterations }).unwrap_or(0);
+			trial.scores = result.map(|(_, scores)| { scores });
+		}
+		rank(&mut remaining, simulations.len());
+		if round + 1 < rounds {
+			let kept = remaining.len().div_ceil(2);
+			let mut dropped = remaining.split_off(kept);
+			dropped.append(&mut eliminated);
+			eliminated = dropped;
+		}
+	}
+	remaining.append(&mut eliminated);
+	Ok(Results { files, trials: remaining })
+}
+
+// The base config with the best trial's values
+pub fn best_config(base_config: &str, results: &Results) -> Result<String, String> {
+	let best = results.trials
+		.iter()
+		.find(|trial| { trial.scores.is_ok() })
+		.ok_or("No candidate config succeeded")?;
+	let content: Value = serde_json::from_str(&candidate_config(base_config, &best.params)?)
+		.map_err(|err| { err.to_string() })?;
+	serde_json::to_string_pretty(&content).map_err(|err| { err.to_string() })
+}
+
+impl fmt::Display for Results {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:<4} {:>6} {:>6} {:>10}", "#", "round", "rank", "iterations")?;
+		for file in &self.files {
+			write!(f, " {:>12}", file.rsplit('/').next().unwrap_or(file))?;
+		}
+		writeln!(f, "  params")?;
+		for (i, trial) in self.trials.iter().enumerate() {
+			write!(f, "{:<4} {:>6} {:>6.2} {:>10}", i, trial.round, trial.rank, trial.iterations)?;
+			let params: Vec<String> = trial.params.iter().map(|(key, value)| { format!("{}={}", key, value) }).collect();
+			match &trial.scores {
+				Ok (scores) => {
+					for score in scores {
+						write!(f, " {:>12.1}", score)?;
+					}
+					writeln!(f, "  {}", params.join(" "))?;
+				},
+				Err (err) => writeln!(f, "  {}  failed: {}", params.join(" "), err)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::read_to_string;
+
+	use serde_json::Value;
+
+	use crate::{ast::parse, control::Control};
+	use super::{Options, Search, Space, best_config, tune};
+
+	#[test]
+	fn test_grid_halving() {
+		let simulation = parse(read_to_string("ressources/ikea").unwrap()).unwrap();
+		let mut space = Space::new();
+		space.insert("max_depth".to_string(), vec![Value::from(1), Value::from(20)]);
+		space.insert("mutation_chance".to_string(), vec![Value::from(0.1), Value::from(2.)]);
+		let options = Options { search: Search::Grid, halving: true, seeds: 1, seed: 0 };
+		let base_config = "{ \"generation_size\": 10, \"iterations\": 4 }";
+		let results = tune(vec!["ikea".to_string()], &[simulation], base_config, &space, &options, &Control::default()).unwrap();
+
+		assert_eq!(results.trials.len(), 4);
+		// Invalid mutation chances fail and come last
+		assert!(results.trials[2].scores.is_err() && results.trials[3].scores.is_err());
+		assert_eq!(results.trials[0].params["max_depth"], Value::from(20));
+		assert_eq!(results.trials[0].iterations, 4);
+		let best: Value = serde_json::from_str(&best_config(base_config, &results).unwrap()).unwrap();
+		assert_eq!(best["max_depth"], Value::from(20));
+		assert_eq!(best["generation_size"], Value::from(10));
+	}
+}
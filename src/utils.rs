@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::thread;
 
 pub fn generalize_error<T: Debug>(err: T) -> String {
 	format!("{:?}", err)
@@ -28,6 +29,49 @@ pub fn fibonacci_n(n: usize) -> Vec<usize> {
 	list
 }
 
+// Number of threads to use, 0 meaning one per core
+pub fn worker_count(workers: usize) -> usize {
+	if workers > 0 {
+		workers
+	} else {
+		thread::available_parallelism().map(|count| { count.get() }).unwrap_or(1)
+	}
+}
+
+// Map over contiguous chunks in `workers` threads, the results keep the items order
+pub fn parallel_map<T: Sync, U: Send, F: Fn(&T) -> U + Sync>(items: &[T], workers: usize, f: F) -> Vec<U> {
+	if workers <= 1 || items.len() <= 1 {
+		return items.iter().map(&f).collect()
+	}
+	let chunk_size = items.len().div_ceil(workers);
+	let f = &f;
+	thread::scope(|scope| {
+		let handles: Vec<_> = items
+			.chunks(chunk_size)
+			.map(|chunk| { scope.spawn(move || { chunk.iter().map(f).collect::<Vec<U>>() }) })
+			.collect();
+		handles
+			.into_iter()
+			.flat_map(|handle| { handle.join().unwrap_or_else(|err| { std::panic::resume_unwind(err) }) })
+			.collect()
+	})
+}
+
+// Insertions, deletions and substitutions turning a into b
+pub fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	let mut current = vec![0; b.len() + 1];
+	for (i, a_item) in a.iter().enumerate() {
+		current[0] = i + 1;
+		for (j, b_item) in b.iter().enumerate() {
+			let substitution = previous[j] + if a_item == b_item { 0 } else { 1 };
+			current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+		}
+		std::mem::swap(&mut previous, &mut current);
+	}
+	previous[b.len()]
+}
+
 fn unite<T>(mut a: Vec<T>, mut b: Vec<T>) -> Vec<T> where T: Ord {
     let mut finish: Vec<T> = Vec::<T>::with_capacity(a.len() + b.len());
 
@@ -69,7 +113,7 @@ pub fn merge_sort<T>(mut vec: Vec<T>) -> Vec<T> where T: Ord {
 
 #[cfg(test)]
 mod tests {
-    use super::merge_sort;
+    use super::{edit_distance, merge_sort, parallel_map};
     use rand::{
         seq::SliceRandom,
         thread_rng,
@@ -106,4 +150,22 @@ mod tests {
 
         assert_eq!(merge_sort(v1), Vec::<i32>::new());
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(&[1, 2, 3], &[1, 2, 3]), 0);
+        assert_eq!(edit_distance(&[1, 2, 3], &[2, 3]), 1);
+        assert_eq!(edit_distance(&[1, 2, 3], &[1, 4, 3, 5]), 2);
+        assert_eq!(edit_distance::<i32>(&[], &[1, 2]), 2);
+    }
+
+    #[test]
+    fn test_parallel_map_keeps_order() {
+        let items: Vec<usize> = (0..103).collect();
+        let expected: Vec<usize> = items.iter().map(|i| i * 2).collect();
+
+        for workers in 1..6 {
+            assert_eq!(parallel_map(&items, workers, |i| i * 2), expected);
+        }
+    }
 }